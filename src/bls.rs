@@ -0,0 +1,483 @@
+//! BLS signatures over BLS12-377 with signatures in G1 and public keys in G2,
+//! and the circuits verifying them inside the BW6-761 scalar field. The
+//! aggregate circuit also supports the opposite placement, with keys in G1
+//! and signatures in G2.
+use super::hash_to_curve::{HashToCurve, HashToCurveGadget};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, One, Zero};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    bits::boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    groups::CurveVar,
+    pairing::PairingVar,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::poseidon::PoseidonParameters;
+use ark_std::{
+    marker::PhantomData,
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+use std::{cmp::Ordering, ops::Neg};
+
+pub fn keygen<I: PairingEngine, R: Rng + CryptoRng>(rng: &mut R) -> (I::Fr, I::G2Affine) {
+    let sk = I::Fr::rand(rng);
+    let pk = I::G2Affine::prime_subgroup_generator()
+        .mul(sk)
+        .into_affine();
+    (sk, pk)
+}
+
+pub fn sign<I: HashToCurve>(
+    params: &PoseidonParameters<I::Fq>,
+    sk: &I::Fr,
+    msg: &[I::Fq],
+) -> I::G1Affine {
+    I::hash_to_g1(params, msg).mul(*sk).into_affine()
+}
+
+/// Checks e(sig, g2) == e(H(m), pk) with a single final exponentiation.
+pub fn verify<I: HashToCurve>(
+    params: &PoseidonParameters<I::Fq>,
+    pk: &I::G2Affine,
+    msg: &[I::Fq],
+    sig: &I::G1Affine,
+) -> bool {
+    let h = I::hash_to_g1(params, msg);
+    I::product_of_pairings(&[
+        (
+            sig.neg().into(),
+            I::G2Affine::prime_subgroup_generator().into(),
+        ),
+        (h.into(), (*pk).into()),
+    ])
+    .is_one()
+}
+
+#[allow(dead_code)]
+pub fn keygen_g1<I: PairingEngine, R: Rng + CryptoRng>(rng: &mut R) -> (I::Fr, I::G1Affine) {
+    let sk = I::Fr::rand(rng);
+    let pk = I::G1Affine::prime_subgroup_generator()
+        .mul(sk)
+        .into_affine();
+    (sk, pk)
+}
+
+#[allow(dead_code)]
+pub fn sign_g2<I: HashToCurve>(
+    params: &PoseidonParameters<I::Fq>,
+    sk: &I::Fr,
+    msg: &[I::Fq],
+) -> I::G2Affine {
+    I::hash_to_g2(params, msg).mul(*sk).into_affine()
+}
+
+/// Checks e(g1, sig) == e(pk, H(m)) for keys in G1 and signatures in G2.
+#[allow(dead_code)]
+pub fn verify_g1_key<I: HashToCurve>(
+    params: &PoseidonParameters<I::Fq>,
+    pk: &I::G1Affine,
+    msg: &[I::Fq],
+    sig: &I::G2Affine,
+) -> bool {
+    let h = I::hash_to_g2(params, msg);
+    I::product_of_pairings(&[
+        (
+            I::G1Affine::prime_subgroup_generator().neg().into(),
+            (*sig).into(),
+        ),
+        ((*pk).into(), h.into()),
+    ])
+    .is_one()
+}
+
+/// Sums the points selected by the bitmap, used for both keys and signatures.
+#[allow(dead_code)]
+pub fn aggregate<G: AffineCurve>(points: &[G], bitmap: &[bool]) -> G {
+    points
+        .iter()
+        .zip(bitmap)
+        .filter(|(_, b)| **b)
+        .fold(G::Projective::zero(), |acc, (p, _)| acc.add_mixed(p))
+        .into_affine()
+}
+
+/// Public key and message variables of the verification circuit.
+type BlsInputs<I, IV> = (
+    <IV as PairingVar<I>>::G2Var,
+    Vec<FpVar<<I as PairingEngine>::Fq>>,
+);
+
+/// Verifies a BLS signature on a public key and message given as public
+/// inputs. The public key is not subgroup checked in circuit since the
+/// verifier knows it.
+pub struct BlsVerifyCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    pk: I::G2Affine,
+    msg: Vec<I::Fq>,
+    sig: I::G1Affine,
+    poseidon_params: PoseidonParameters<I::Fq>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> BlsVerifyCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(
+        pk: I::G2Affine,
+        msg: Vec<I::Fq>,
+        sig: I::G1Affine,
+        params: PoseidonParameters<I::Fq>,
+    ) -> Self {
+        Self {
+            pk,
+            msg,
+            sig,
+            poseidon_params: params,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        pk: I::G2Affine,
+        msg: &[I::Fq],
+    ) -> Result<BlsInputs<I, IV>, SynthesisError> {
+        let pk = IV::G2Var::new_input(ns!(cs, "pk"), || Ok(pk))?;
+        let msg = Vec::<FpVar<I::Fq>>::new_input(ns!(cs, "msg"), || Ok(msg.to_vec()))?;
+        Ok((pk, msg))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(pk: I::G2Affine, msg: &[I::Fq]) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        Self::alloc_inputs(cs.clone(), pk, msg)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+/// Enforces e(sig, g2) == e(h, pk) as a product of two Miller loops sharing
+/// one final exponentiation.
+pub fn enforce_bls_verify<I, IV>(
+    pk: &IV::G2Var,
+    h: &IV::G1Var,
+    sig: &IV::G1Var,
+) -> Result<(), SynthesisError>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    let g2 = IV::G2Var::constant(I::G2Projective::prime_subgroup_generator());
+    let ps = [IV::prepare_g1(&sig.negate()?)?, IV::prepare_g1(h)?];
+    let qs = [IV::prepare_g2(&g2)?, IV::prepare_g2(pk)?];
+    let ml = IV::miller_loop(&ps, &qs)?;
+    IV::final_exponentiation(&ml)?.enforce_equal(&IV::GTVar::one())
+}
+
+/// Same as [`enforce_bls_verify`] with keys in G1 and signatures in G2:
+/// e(g1, sig) == e(pk, h).
+pub fn enforce_bls_verify_g1_key<I, IV>(
+    pk: &IV::G1Var,
+    h: &IV::G2Var,
+    sig: &IV::G2Var,
+) -> Result<(), SynthesisError>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    let g1 = IV::G1Var::constant(I::G1Projective::prime_subgroup_generator());
+    let ps = [IV::prepare_g1(&g1.negate()?)?, IV::prepare_g1(pk)?];
+    let qs = [IV::prepare_g2(sig)?, IV::prepare_g2(h)?];
+    let ml = IV::miller_loop(&ps, &qs)?;
+    IV::final_exponentiation(&ml)?.enforce_equal(&IV::GTVar::one())
+}
+
+/// Sums the points whose bit is set, with one conditional addition per point.
+pub fn conditional_sum<C, CF, V>(points: &[V], bits: &[Boolean<CF>]) -> Result<V, SynthesisError>
+where
+    C: ProjectiveCurve,
+    CF: Field,
+    V: CurveVar<C, CF>,
+{
+    let mut acc = V::zero();
+    for (p, b) in points.iter().zip(bits) {
+        acc = b.select(&(acc.clone() + p), &acc)?;
+    }
+    Ok(acc)
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for BlsVerifyCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let (pk, msg) = Self::alloc_inputs(cs.clone(), self.pk, &self.msg)?;
+        let sig = IV::G1Var::new_witness(ns!(cs, "sig"), || Ok(self.sig))?;
+        let h = IV::hash_to_g1(&self.poseidon_params, &msg)?;
+        enforce_bls_verify::<I, IV>(&pk, &h, &sig)
+    }
+}
+
+/// Keys of the signers and the aggregate signature, in either placement.
+#[derive(Clone)]
+#[allow(dead_code)]
+pub enum AggregateKeys<I: PairingEngine> {
+    /// Keys in G2, signatures in G1.
+    G2 {
+        pks: Vec<I::G2Affine>,
+        sig: I::G1Affine,
+    },
+    /// Keys in G1, signatures in G2.
+    G1 {
+        pks: Vec<I::G1Affine>,
+        sig: I::G2Affine,
+    },
+}
+
+/// Light-client style verification: the n public keys, the participation
+/// bitmap and the message are public inputs, the aggregate signature is a
+/// witness. The circuit aggregates the keys selected by the bitmap, enforces
+/// that at least `threshold` signers participated and verifies the aggregate
+/// signature with a single pairing check.
+pub struct AggregateBlsCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    keys: AggregateKeys<I>,
+    bitmap: Vec<bool>,
+    threshold: usize,
+    msg: Vec<I::Fq>,
+    poseidon_params: PoseidonParameters<I::Fq>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> AggregateBlsCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(
+        keys: AggregateKeys<I>,
+        bitmap: Vec<bool>,
+        threshold: usize,
+        msg: Vec<I::Fq>,
+        params: PoseidonParameters<I::Fq>,
+    ) -> Self {
+        assert!(threshold > 0, "an empty aggregate key verifies nothing");
+        Self {
+            keys,
+            bitmap,
+            threshold,
+            msg,
+            poseidon_params: params,
+            _iv: PhantomData,
+        }
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for AggregateBlsCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let bitmap = Vec::<Boolean<I::Fq>>::new_input(ns!(cs, "bitmap"), || Ok(self.bitmap))?;
+        let msg = Vec::<FpVar<I::Fq>>::new_input(ns!(cs, "msg"), || Ok(self.msg))?;
+        let count = bitmap
+            .iter()
+            .fold(FpVar::zero(), |acc, b| acc + FpVar::from(b.clone()));
+        let threshold = FpVar::constant(I::Fq::from(self.threshold as u64));
+        count.enforce_cmp(&threshold, Ordering::Greater, true)?;
+        match self.keys {
+            AggregateKeys::G2 { pks, sig } => {
+                let pks = Vec::<IV::G2Var>::new_input(ns!(cs, "pks"), || Ok(pks))?;
+                let apk = conditional_sum(&pks, &bitmap)?;
+                let sig = IV::G1Var::new_witness(ns!(cs, "sig"), || Ok(sig))?;
+                let h = IV::hash_to_g1(&self.poseidon_params, &msg)?;
+                enforce_bls_verify::<I, IV>(&apk, &h, &sig)
+            }
+            AggregateKeys::G1 { pks, sig } => {
+                let pks = Vec::<IV::G1Var>::new_input(ns!(cs, "pks"), || Ok(pks))?;
+                let apk = conditional_sum(&pks, &bitmap)?;
+                let sig = IV::G2Var::new_witness(ns!(cs, "sig"), || Ok(sig))?;
+                let h = IV::hash_to_g2(&self.poseidon_params, &msg)?;
+                enforce_bls_verify_g1_key::<I, IV>(&apk, &h, &sig)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq};
+    use ark_bw6_761::BW6_761;
+    use ark_groth16::Groth16;
+    use ark_snark::SNARK;
+    use std::time::Instant;
+
+    type Circuit = BlsVerifyCircuit<I, IV>;
+
+    #[test]
+    fn native_bls() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (sk, pk) = keygen::<I, _>(&mut rng);
+        let msg = vec![Fq::rand(&mut rng), Fq::rand(&mut rng)];
+        let sig = sign::<I>(&params, &sk, &msg);
+        assert!(verify::<I>(&params, &pk, &msg, &sig));
+        let other = vec![Fq::rand(&mut rng)];
+        assert!(!verify::<I>(&params, &pk, &other, &sig));
+    }
+
+    #[test]
+    fn bls_verify_circuit() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (sk, pk) = keygen::<I, _>(&mut rng);
+        let msg = vec![Fq::rand(&mut rng), Fq::rand(&mut rng)];
+        let sig = sign::<I>(&params, &sk, &msg);
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(pk, msg.clone(), sig, params.clone())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        println!("BLS verification: {} constraints", cs.num_constraints());
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let wrong = sign::<I>(&params, &sk, &[Fq::rand(&mut rng)]);
+        Circuit::new(pk, msg, wrong, params)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn bls_verify_groth16() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (sk, pk) = keygen::<I, _>(&mut rng);
+        let msg = vec![Fq::rand(&mut rng), Fq::rand(&mut rng)];
+        let sig = sign::<I>(&params, &sk, &msg);
+
+        let start = Instant::now();
+        let (ppk, pvk) = Groth16::<BW6_761>::circuit_specific_setup(
+            Circuit::new(pk, msg.clone(), sig, params.clone()),
+            &mut rng,
+        )
+        .unwrap();
+        println!("BW6-761 Groth16 setup: {:?}", start.elapsed());
+        let start = Instant::now();
+        let proof =
+            Groth16::<BW6_761>::prove(&ppk, Circuit::new(pk, msg.clone(), sig, params), &mut rng)
+                .unwrap();
+        println!("BW6-761 Groth16 proving: {:?}", start.elapsed());
+        let inputs = Circuit::public_inputs(pk, &msg).unwrap();
+        let start = Instant::now();
+        assert!(Groth16::<BW6_761>::verify(&pvk, &inputs, &proof).unwrap());
+        println!("BW6-761 Groth16 verification: {:?}", start.elapsed());
+    }
+
+    fn aggregate_circuit<R: Rng + CryptoRng>(
+        rng: &mut R,
+        params: &PoseidonParameters<Fq>,
+        n: usize,
+        g1_keys: bool,
+    ) -> AggregateBlsCircuit<I, IV> {
+        let msg = vec![Fq::rand(rng), Fq::rand(rng)];
+        // two thirds of the committee sign
+        let bitmap = (0..n).map(|i| i % 3 != 0).collect::<Vec<_>>();
+        let threshold = bitmap.iter().filter(|b| **b).count();
+        let keys = if g1_keys {
+            let (sks, pks): (Vec<_>, Vec<_>) = (0..n).map(|_| keygen_g1::<I, _>(rng)).unzip();
+            let sigs = sks
+                .iter()
+                .map(|sk| sign_g2::<I>(params, sk, &msg))
+                .collect::<Vec<_>>();
+            let sig = aggregate(&sigs, &bitmap);
+            assert!(verify_g1_key::<I>(
+                params,
+                &aggregate(&pks, &bitmap),
+                &msg,
+                &sig
+            ));
+            AggregateKeys::G1 { pks, sig }
+        } else {
+            let (sks, pks): (Vec<_>, Vec<_>) = (0..n).map(|_| keygen::<I, _>(rng)).unzip();
+            let sigs = sks
+                .iter()
+                .map(|sk| sign::<I>(params, sk, &msg))
+                .collect::<Vec<_>>();
+            let sig = aggregate(&sigs, &bitmap);
+            assert!(verify::<I>(params, &aggregate(&pks, &bitmap), &msg, &sig));
+            AggregateKeys::G2 { pks, sig }
+        };
+        AggregateBlsCircuit::new(keys, bitmap, threshold, msg, params.clone())
+    }
+
+    #[test]
+    fn aggregate_bls_circuit() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        for g1_keys in [false, true] {
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            aggregate_circuit(&mut rng, &params, 6, g1_keys)
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+
+            // one participant short of the threshold
+            let mut circuit = aggregate_circuit(&mut rng, &params, 6, g1_keys);
+            circuit.threshold += 1;
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert!(!cs.is_satisfied().unwrap());
+
+            // a bitmap that does not match the aggregate signature
+            let mut circuit = aggregate_circuit(&mut rng, &params, 6, g1_keys);
+            circuit.bitmap[0] = true;
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert!(!cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn bench_aggregate_bls() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        for g1_keys in [false, true] {
+            for n in [16, 64, 256, 1024] {
+                let cs = ConstraintSystem::<Fq>::new_ref();
+                aggregate_circuit(&mut rng, &params, n, g1_keys)
+                    .generate_constraints(cs.clone())
+                    .unwrap();
+                assert!(cs.is_satisfied().unwrap());
+                println!(
+                    "aggregate BLS, keys in {}, n = {}: {} constraints",
+                    if g1_keys { "G1" } else { "G2" },
+                    n,
+                    cs.num_constraints()
+                );
+            }
+        }
+    }
+}
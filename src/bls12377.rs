@@ -1,31 +1,88 @@
-use super::poseidon;
+use super::elgamal;
+use super::hash_to_curve::{HashToCurve, HashToCurveGadget};
+use super::kzg::{self, Opening, OpeningVar, VerifierKey};
+use super::lagrange;
+use super::limbs::LimbVar;
+use super::merkle::{self, MerklePath, MerklePathVar};
+use super::nonnative_cmp;
+use super::packing;
+use super::pedersen;
+use super::recursion::{self, InnerProof};
+use super::threshold;
 use super::OpMode;
-use ark_ec::bls12;
 use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
-use ark_ff::{BigInteger, PrimeField};
-use ark_nonnative_field::NonNativeFieldVar;
+use ark_ff::{BigInteger, FpParameters, PrimeField, Zero};
+use ark_nonnative_field::{NonNativeFieldMulResultVar, NonNativeFieldParams, NonNativeFieldVar};
 use ark_r1cs_std::{
     alloc::AllocVar,
+    bits::boolean::Boolean,
     eq::EqGadget,
     fields::{fp::FpVar, FieldVar},
     groups::CurveVar,
     pairing::PairingVar,
-    ToBitsGadget, ToConstraintFieldGadget,
+    select::CondSelectGadget,
+    ToBitsGadget, ToBytesGadget, ToConstraintFieldGadget,
 };
 use ark_relations::{
     ns,
     r1cs::{ConstraintSynthesizer, ConstraintSystemRef, Field, SynthesisError},
 };
-use ark_sponge::constraints::CryptographicSpongeVar;
+use ark_sponge::constraints::{bits_le_to_nonnative, CryptographicSpongeVar};
+use ark_sponge::poseidon::constraints::PoseidonSpongeVar;
 use ark_sponge::poseidon::PoseidonParameters;
-use ark_sponge::{poseidon::constraints::PoseidonSpongeVar, CryptographicSponge};
+use ark_sponge::Absorb;
 use ark_std::{
+    cmp::Ordering,
     marker::PhantomData,
     rand::{CryptoRng, Rng},
     UniformRand,
 };
-use eyre::{Result, WrapErr};
+use eyre::Result;
 use std::ops::MulAssign;
+/// Size of the challenges of `OpMode::NNAShortChallenges`.
+const SHORT_CHALLENGE_BITS: usize = 128;
+
+/// Participant indices, their shares D_i = T^f(x_i) of a random polynomial
+/// of degree t - 1 and the combination T^f(0).
+type LagrangeShares<I> = (
+    Vec<<I as PairingEngine>::Fr>,
+    Vec<<I as PairingEngine>::Fqk>,
+    <I as PairingEngine>::Fqk,
+);
+
+fn lagrange_shares<I: PairingEngine, R: Rng + CryptoRng>(
+    rng: &mut R,
+    base: &I::Fqk,
+    t: usize,
+) -> LagrangeShares<I> {
+    let coeffs = (0..t).map(|_| I::Fr::rand(rng)).collect::<Vec<_>>();
+    let eval = |x: &I::Fr| {
+        coeffs
+            .iter()
+            .rev()
+            .fold(I::Fr::zero(), |acc, c| acc * x + c)
+    };
+    // odd indices, as if every other participant answered
+    let indices = (0..t)
+        .map(|i| I::Fr::from(2 * i as u64 + 1))
+        .collect::<Vec<_>>();
+    let shares = indices
+        .iter()
+        .map(|x| base.pow(eval(x).into_repr()))
+        .collect();
+    let combined = base.pow(coeffs[0].into_repr());
+    (indices, shares, combined)
+}
+
+/// Polynomial coefficients, secret first, and its evaluations at 1..=t.
+type ShamirVectors<I> = (
+    Vec<<I as PairingEngine>::Fr>,
+    Vec<(<I as PairingEngine>::Fr, <I as PairingEngine>::Fr)>,
+);
+
+/// The two vectors of an inner product.
+type InnerProductVectors<I> = (Vec<<I as PairingEngine>::Fr>, Vec<<I as PairingEngine>::Fr>);
+
 struct FqCircuit<I, IV>
 where
     I: PairingEngine,
@@ -38,7 +95,15 @@ where
     t: I::Fqk,
     ag: I::G1Projective,
     bg: I::G2Projective,
+    #[allow(dead_code)]
     miller_out: I::Fqk,
+    msg: Vec<I::Fq>,
+    inner: Option<InnerProof<I>>,
+    kzg: Option<(VerifierKey<I>, Vec<Opening<I>>)>,
+    lagrange: Option<LagrangeShares<I>>,
+    shamir: Option<ShamirVectors<I>>,
+    merkle: Option<(I::Fq, MerklePath<I::Fq>)>,
+    vectors: Option<InnerProductVectors<I>>,
     mode: OpMode,
     poseidon_params: PoseidonParameters<I::Fq>,
     _iv: PhantomData<IV>,
@@ -80,20 +145,60 @@ where
             I::G2Affine::prime_subgroup_generator().into(),
         )]);
         // CT = GT^c = GT^{a+b} = GT^a * GT^b
-        let ct = t.pow(&c.into_repr());
+        let ct = t.pow(c.into_repr());
         assert_eq!(abt, ct);
-        let ct2 = t.pow(&a.into_repr()) * t.pow(&b.into_repr());
+        let ct2 = t.pow(a.into_repr()) * t.pow(b.into_repr());
         assert_eq!(ct, ct2);
+        let msg = (0..2).map(|_| I::Fq::rand(&mut rng)).collect();
+        let inner = match mode {
+            OpMode::Groth16Verify(n) => Some(recursion::inner_proof::<I, _>(rng, n)),
+            _ => None,
+        };
+        let kzg = match mode {
+            OpMode::KzgVerify(n) => {
+                let pp = kzg::setup::<I, _>(rng, 15);
+                let openings = kzg::random_openings(rng, &pp, n, 15);
+                Some((pp.vk().clone(), openings))
+            }
+            _ => None,
+        };
+        let lagrange = match mode {
+            OpMode::GtLagrangeCombine(t) => Some(lagrange_shares::<I, _>(rng, &at, t)),
+            _ => None,
+        };
+        let shamir = match mode {
+            OpMode::NNAPolyEval(d) => Some(threshold::shamir_share(rng, c, d + 1, d + 1)),
+            OpMode::NNAShamirReconstruct(t) => Some(threshold::shamir_share(rng, c, t, t)),
+            _ => None,
+        };
+        let merkle = match mode {
+            OpMode::MerklePath(depth, arity) => Some(merkle::random_path(rng, arity, depth)),
+            _ => None,
+        };
+        let vectors = match mode {
+            OpMode::NNAInnerProduct(n) | OpMode::NNAInnerProductNaive(n) => Some((
+                (0..n).map(|_| I::Fr::rand(&mut rng)).collect(),
+                (0..n).map(|_| I::Fr::rand(&mut rng)).collect(),
+            )),
+            _ => None,
+        };
         Self {
-            mode: mode,
-            c: c,
-            at: at,
-            bt: bt,
-            ct: ct,
-            ag: ag,
+            mode,
+            c,
+            at,
+            bt,
+            ct,
+            ag,
             bg: I::G2Projective::prime_subgroup_generator(),
-            miller_out: miller_out,
-            t: t,
+            miller_out,
+            msg,
+            inner,
+            kzg,
+            lagrange,
+            shamir,
+            merkle,
+            vectors,
+            t,
             poseidon_params: params,
             _iv: PhantomData,
             _i: PhantomData,
@@ -103,9 +208,11 @@ where
 
 impl<I, IV> ConstraintSynthesizer<I::Fq> for FqCircuit<I, IV>
 where
-    I: PairingEngine,
-    IV: PairingVar<I>,
+    I: HashToCurve,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+    IV::G1Var: ToConstraintFieldGadget<I::Fq>,
     IV::GTVar: ToConstraintFieldGadget<I::Fq>,
+    I::Fq: Absorb,
 {
     fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
         match self.mode {
@@ -143,21 +250,142 @@ where
             OpMode::HashGT(n) => {
                 let mut sponge = PoseidonSpongeVar::new(cs.clone(), &self.poseidon_params);
                 let at = IV::GTVar::new_witness(ns!(cs, "a"), || Ok(self.at))?;
-                for i in 0..n {
-                    sponge.absorb(&at.to_constraint_field()?);
+                for _ in 0..n {
+                    sponge.absorb(&at.to_constraint_field()?)?;
                 }
-                sponge.squeeze_field_elements(1)?.remove(0);
+                let _ = sponge.squeeze_field_elements(1)?.remove(0);
             }
             OpMode::NNAHash(n) => {
                 let mut sponge = PoseidonSpongeVar::new(cs.clone(), &self.poseidon_params);
                 let cv = NonNativeFieldVar::<I::Fr, I::Fq>::new_witness(
                     ark_relations::ns!(cs, "share_nonnative"),
-                    || Ok(self.c.clone()),
+                    || Ok(self.c),
+                )?;
+                for _ in 0..n {
+                    sponge.absorb(&cv.to_constraint_field()?)?;
+                }
+                sponge.squeeze_nonnative_field_elements::<I::Fr>(1)?;
+            }
+            OpMode::NNAHashPacked(n) => {
+                let mut sponge = PoseidonSpongeVar::new(cs.clone(), &self.poseidon_params);
+                let cv = NonNativeFieldVar::<I::Fr, I::Fq>::new_witness(
+                    ark_relations::ns!(cs, "share_nonnative"),
+                    || Ok(self.c),
                 )?;
-                for i in 0..n {
-                    sponge.absorb(&cv.to_constraint_field()?);
+                // decomposed once, the bits are reused for every copy
+                let bits = packing::bits_var(&cv)?;
+                let bits = (0..n).flat_map(|_| bits.clone()).collect::<Vec<_>>();
+                sponge.absorb(&packing::pack_bits_var(&bits)?)?;
+                sponge.squeeze_nonnative_field_elements::<I::Fr>(1)?;
+            }
+            OpMode::NNAChallenges(k)
+            | OpMode::NNAShortChallenges(k)
+            | OpMode::FqTruncatedChallenges(k) => {
+                let msg = Vec::<FpVar<I::Fq>>::new_witness(ns!(cs, "msg"), || Ok(self.msg))?;
+                let mut sponge = PoseidonSpongeVar::new(cs.clone(), &self.poseidon_params);
+                sponge.absorb(&msg)?;
+                match self.mode {
+                    OpMode::NNAChallenges(_) => {
+                        sponge.squeeze_nonnative_field_elements::<I::Fr>(k)?;
+                    }
+                    OpMode::NNAShortChallenges(_) => {
+                        // ark-sponge ignores `FieldElementSize::Truncated` and
+                        // squeezes full elements, so the bits are cut here
+                        let bits = sponge.squeeze_bits(SHORT_CHALLENGE_BITS * k)?;
+                        let bits = bits
+                            .chunks(SHORT_CHALLENGE_BITS)
+                            .map(<[_]>::to_vec)
+                            .collect::<Vec<_>>();
+                        bits_le_to_nonnative::<I::Fr, I::Fq>(cs.clone(), &bits)?;
+                    }
+                    _ => {
+                        // one Fq element per challenge, keeping the low bits
+                        // below the Fr modulus, as scalar multiplication takes
+                        let bits = sponge
+                            .squeeze_field_elements(k)?
+                            .iter()
+                            .map(|e| {
+                                let mut bits = e.to_bits_le()?;
+                                bits.truncate(<I::Fr as PrimeField>::Params::CAPACITY as usize);
+                                Ok(bits)
+                            })
+                            .collect::<Result<Vec<_>, SynthesisError>>()?;
+                        bits_le_to_nonnative::<I::Fr, I::Fq>(cs.clone(), &bits)?;
+                    }
                 }
-                sponge.squeeze_nonnative_field_elements::<I::Fr>(1);
+            }
+            OpMode::PedersenCommit(n) => {
+                let pp = pedersen::setup::<I>(&self.poseidon_params, n);
+                let (m, r) = (vec![self.c; n], self.c);
+                let c = pedersen::commit::<I>(&pp, &m, &r);
+                let c = IV::G1Var::new_input(ns!(cs, "commitment"), || Ok(c.into_projective()))?;
+                let m = m
+                    .iter()
+                    .map(|m| elgamal::alloc_scalar_bits::<I>(cs.clone(), Some(*m)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let r = elgamal::alloc_scalar_bits::<I>(cs.clone(), Some(r))?;
+                pedersen::commit_var::<I, IV>(&pp, &m, &r)?.enforce_equal(&c)?;
+            }
+            OpMode::NNAPolyEval(_) => {
+                let (coeffs, shares) = self.shamir.unwrap();
+                let (x, y) = shares[shares.len() - 1];
+                let coeffs =
+                    Vec::<NonNativeFieldVar<I::Fr, I::Fq>>::new_witness(ns!(cs, "coeffs"), || {
+                        Ok(coeffs)
+                    })?;
+                let x = NonNativeFieldVar::new_witness(ns!(cs, "x"), || Ok(x))?;
+                let y = NonNativeFieldVar::new_witness(ns!(cs, "y"), || Ok(y))?;
+                threshold::evaluate_var(&coeffs, &x)?.enforce_equal(&y)?;
+            }
+            OpMode::NNAShamirReconstruct(_) => {
+                let (_, shares) = self.shamir.unwrap();
+                let (indices, shares): (Vec<_>, Vec<_>) = shares.into_iter().unzip();
+                let indices =
+                    Vec::<NonNativeFieldVar<I::Fr, I::Fq>>::new_input(ns!(cs, "indices"), || {
+                        Ok(indices)
+                    })?;
+                let shares =
+                    Vec::<NonNativeFieldVar<I::Fr, I::Fq>>::new_witness(ns!(cs, "shares"), || {
+                        Ok(shares)
+                    })?;
+                let secret = NonNativeFieldVar::new_witness(ns!(cs, "secret"), || Ok(self.c))?;
+                threshold::shamir_reconstruct_var(&indices, &shares)?.enforce_equal(&secret)?;
+            }
+            OpMode::NNAInnerProduct(_) | OpMode::NNAInnerProductNaive(_) => {
+                let (a, b) = self.vectors.unwrap();
+                let expected = a.iter().zip(&b).map(|(a, b)| *a * b).sum::<I::Fr>();
+                let a =
+                    Vec::<NonNativeFieldVar<I::Fr, I::Fq>>::new_witness(ns!(cs, "a"), || Ok(a))?;
+                let b =
+                    Vec::<NonNativeFieldVar<I::Fr, I::Fq>>::new_witness(ns!(cs, "b"), || Ok(b))?;
+                let expected =
+                    NonNativeFieldVar::new_witness(ns!(cs, "expected"), || Ok(expected))?;
+                let result = if let OpMode::NNAInnerProduct(_) = self.mode {
+                    // the products are summed unreduced and reduced once
+                    let mut acc = NonNativeFieldMulResultVar::zero();
+                    for (a, b) in a.iter().zip(&b) {
+                        acc += &a.mul_without_reduce(b)?;
+                    }
+                    acc.reduce()?
+                } else {
+                    let mut acc = NonNativeFieldVar::zero();
+                    for (a, b) in a.iter().zip(&b) {
+                        acc += a * b;
+                    }
+                    acc
+                };
+                result.enforce_equal(&expected)?;
+            }
+            OpMode::MerklePath(..) => {
+                let (leaf, path) = self.merkle.unwrap();
+                let root = path.root(&self.poseidon_params, &leaf);
+                let root = FpVar::new_input(ns!(cs, "root"), || Ok(root))?;
+                let leaf = FpVar::new_witness(ns!(cs, "leaf"), || Ok(leaf))?;
+                MerklePathVar::new_witness(cs.clone(), &path)?.enforce_membership(
+                    &self.poseidon_params,
+                    &root,
+                    &leaf,
+                )?;
             }
             OpMode::MillerLoop(n) => {
                 let mut ps = Vec::new();
@@ -173,7 +401,7 @@ where
                 IV::miller_loop(&ps, &qs)?;
             }
             OpMode::FinalExp => {
-                let m = IV::GTVar::new_witness(ns!(cs, "CT"), || Ok(self.ct))?;
+                let _m = IV::GTVar::new_witness(ns!(cs, "CT"), || Ok(self.ct))?;
                 let at = IV::GTVar::new_witness(ns!(cs, "a"), || Ok(self.at))?;
                 IV::final_exponentiation(&at)?;
             }
@@ -184,6 +412,32 @@ where
                 let pbg = IV::prepare_g2(&bg)?;
                 IV::pairing(pag, pbg)?;
             }
+            OpMode::Groth16Verify(_) => {
+                let (vk, proof, inputs) = self.inner.unwrap();
+                recursion::enforce_groth16_verify::<I, IV>(cs, &vk, &inputs, &proof)?;
+            }
+            OpMode::KzgVerify(_) => {
+                let (vk, openings) = self.kzg.unwrap();
+                let openings = openings
+                    .iter()
+                    .map(|o| OpeningVar::<I, IV>::new(cs.clone(), o))
+                    .collect::<Result<Vec<_>, _>>()?;
+                kzg::enforce_batch_verify(cs, &self.poseidon_params, &vk, &openings)?;
+            }
+            OpMode::GtLagrangeCombine(_) => {
+                let (indices, shares, combined) = self.lagrange.unwrap();
+                let indices =
+                    Vec::<NonNativeFieldVar<I::Fr, I::Fq>>::new_input(ns!(cs, "indices"), || {
+                        Ok(indices)
+                    })?;
+                let shares = Vec::<IV::GTVar>::new_witness(ns!(cs, "shares"), || Ok(shares))?;
+                let combined = IV::GTVar::new_witness(ns!(cs, "combined"), || Ok(combined))?;
+                let exps = lagrange::lagrange_at_zero_var(&indices)?
+                    .iter()
+                    .map(|l| Ok(l.to_bits_le()?[..I::Fr::size_in_bits()].to_vec()))
+                    .collect::<Result<Vec<_>, SynthesisError>>()?;
+                combined.enforce_equal(&lagrange::multi_pow_le(&shares, &exps)?)?;
+            }
             OpMode::G1Mul => {
                 let ag = IV::G1Var::new_witness(ns!(cs, "ag"), || Ok(self.ag))?;
                 let scalar_in_fq = &I::Fq::from_repr(<I::Fq as PrimeField>::BigInt::from_bits_le(
@@ -204,28 +458,36 @@ where
                 let bits_c = c.to_bits_le()?;
                 bg.scalar_mul_le(bits_c.iter())?;
             }
+            OpMode::HashToG1 => {
+                let msg = Vec::<FpVar<I::Fq>>::new_witness(ns!(cs, "msg"), || Ok(self.msg))?;
+                IV::hash_to_g1(&self.poseidon_params, &msg)?;
+            }
+            OpMode::HashToG2 => {
+                let msg = Vec::<FpVar<I::Fq>>::new_witness(ns!(cs, "msg"), || Ok(self.msg))?;
+                IV::hash_to_g2(&self.poseidon_params, &msg)?;
+            }
 
             OpMode::NNAFieldAddOverFq => {
                 let cv = NonNativeFieldVar::<I::Fr, I::Fq>::new_witness(
                     ark_relations::ns!(cs, "share_nonnative"),
-                    || Ok(self.c.clone()),
+                    || Ok(self.c),
                 )?;
                 let cv2 = NonNativeFieldVar::<I::Fr, I::Fq>::new_witness(
                     ark_relations::ns!(cs, "share_nonnative"),
                     || Ok(self.c),
                 )?;
-                cv + cv2;
+                let _ = cv + cv2;
             }
             OpMode::NNAFieldMulOverFq => {
                 let cv = NonNativeFieldVar::<I::Fr, I::Fq>::new_witness(
                     ark_relations::ns!(cs, "share_nonnative"),
-                    || Ok(self.c.clone()),
+                    || Ok(self.c),
                 )?;
                 let cv2 = NonNativeFieldVar::<I::Fr, I::Fq>::new_witness(
                     ark_relations::ns!(cs, "share_nonnative"),
                     || Ok(self.c),
                 )?;
-                cv * cv2;
+                let _ = cv * cv2;
             }
             _ => panic!("unsupported operation on bls12377 circuit"),
         };
@@ -237,24 +499,57 @@ where
 enum NNAMode {
     Add,
     Mul,
+    Inverse,
+    Square,
+    EnforceEqual,
+    IsEq,
+    ToBits,
+    ToBytes,
+    EnforceCmp(bool), // enforce_cmp(should_also_check_equality)
+    ConditionalSelect,
 }
 
+#[allow(dead_code)]
+const NNA_MODES: [NNAMode; 11] = [
+    NNAMode::Add,
+    NNAMode::Mul,
+    NNAMode::Inverse,
+    NNAMode::Square,
+    NNAMode::EnforceEqual,
+    NNAMode::IsEq,
+    NNAMode::ToBits,
+    NNAMode::ToBytes,
+    NNAMode::EnforceCmp(false),
+    NNAMode::EnforceCmp(true),
+    NNAMode::ConditionalSelect,
+];
+
 struct NNACircuit<F: PrimeField, CF: PrimeField> {
     e1: F,
     e2: F,
+    #[allow(dead_code)]
     e3: F,
     m: NNAMode,
+    /// Custom limbs replacing the `NonNativeFieldVar` representation.
+    limbs: Option<NonNativeFieldParams>,
     _f1: PhantomData<F>,
     _f2: PhantomData<CF>,
 }
 
+#[allow(dead_code)]
 impl<F: PrimeField, CF: PrimeField> NNACircuit<F, CF> {
+    /// Operands drawn from `test_rng`, so that runs are reproducible.
     fn new(m: NNAMode) -> Self {
-        let e1 = F::rand(&mut rand::thread_rng());
-        let e2 = F::rand(&mut rand::thread_rng());
+        Self::rand(&mut ark_std::test_rng(), m)
+    }
+
+    fn rand<R: Rng>(rng: &mut R, m: NNAMode) -> Self {
+        let e1 = F::rand(rng);
+        let e2 = F::rand(rng);
         let e3 = e1 * e2;
         Self {
             m,
+            limbs: None,
             _f1: PhantomData,
             _f2: PhantomData,
             e1,
@@ -262,25 +557,86 @@ impl<F: PrimeField, CF: PrimeField> NNACircuit<F, CF> {
             e3,
         }
     }
+
+    /// Only `Add` and `Mul` are implemented over custom limbs.
+    fn with_limbs<R: Rng>(rng: &mut R, m: NNAMode, params: NonNativeFieldParams) -> Self {
+        assert!(
+            matches!(m, NNAMode::Add | NNAMode::Mul),
+            "custom limbs only support Add and Mul"
+        );
+        Self {
+            limbs: Some(params),
+            ..Self::rand(rng, m)
+        }
+    }
 }
 
 impl<F: PrimeField, CF: PrimeField> ConstraintSynthesizer<CF> for NNACircuit<F, CF> {
     fn generate_constraints(self, cs: ConstraintSystemRef<CF>) -> Result<(), SynthesisError> {
+        if let Some(params) = &self.limbs {
+            let e1 = LimbVar::<F, CF>::new_witness(cs.clone(), Some(self.e1), params)?;
+            let e2 = LimbVar::<F, CF>::new_witness(cs.clone(), Some(self.e2), params)?;
+            match self.m {
+                NNAMode::Add => {
+                    let _res_e3 = e1.add(&e2);
+                }
+                NNAMode::Mul => {
+                    let _res_e3 = e1.mul(&e2)?;
+                }
+                _ => unreachable!("rejected by with_limbs"),
+            }
+            return Ok(());
+        }
         let nna_e1 = NonNativeFieldVar::<F, CF>::new_witness(
             ark_relations::ns!(cs, "nna_circuit_e1"),
-            || Ok(self.e1.clone()),
+            || Ok(self.e1),
         )?;
         let nna_e2 = NonNativeFieldVar::<F, CF>::new_witness(
             ark_relations::ns!(cs, "nna_circuit_e2"),
-            || Ok(self.e2.clone()),
+            || Ok(self.e2),
         )?;
 
         match self.m {
             NNAMode::Add => {
-                let res_e3 = nna_e1 + nna_e2;
+                let _res_e3 = nna_e1 + nna_e2;
             }
             NNAMode::Mul => {
-                let res_e3 = nna_e1 * nna_e2;
+                let _res_e3 = nna_e1 * nna_e2;
+            }
+            NNAMode::Inverse => {
+                let _res = nna_e1.inverse()?;
+            }
+            NNAMode::Square => {
+                let _res = nna_e1.square()?;
+            }
+            NNAMode::EnforceEqual => {
+                let copy = NonNativeFieldVar::<F, CF>::new_witness(
+                    ark_relations::ns!(cs, "nna_circuit_e1_copy"),
+                    || Ok(self.e1),
+                )?;
+                nna_e1.enforce_equal(&copy)?;
+            }
+            NNAMode::IsEq => {
+                let _res = nna_e1.is_eq(&nna_e2)?;
+            }
+            NNAMode::ToBits => {
+                let _res = nna_e1.to_bits_le()?;
+            }
+            NNAMode::ToBytes => {
+                let _res = nna_e1.to_bytes()?;
+            }
+            NNAMode::EnforceCmp(should_also_check_equality) => {
+                let ordering = if self.e1 < self.e2 {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                };
+                nonnative_cmp::enforce_cmp(&nna_e1, &nna_e2, ordering, should_also_check_equality)?;
+            }
+            NNAMode::ConditionalSelect => {
+                let cond =
+                    Boolean::new_witness(ark_relations::ns!(cs, "nna_circuit_cond"), || Ok(true))?;
+                let _res = NonNativeFieldVar::conditionally_select(&cond, &nna_e1, &nna_e2)?;
             }
         }
         Ok(())
@@ -289,28 +645,32 @@ impl<F: PrimeField, CF: PrimeField> ConstraintSynthesizer<CF> for NNACircuit<F,
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::limbs;
+    use crate::poseidon;
     use ark_bls12_377::{
         constraints::PairingVar as IV, Bls12_377 as I, Fr, G1Projective as G1, G2Projective as G2,
     };
-    use ark_relations::r1cs::ConstraintSystem;
+    use ark_nonnative_field::params::OptimizationType;
+    use ark_relations::r1cs::{ConstraintSystem, OptimizationGoal};
     use ark_serialize::CanonicalSerialize;
     use ark_std::One;
 
     #[test]
-    fn sizes() {
+    fn sizes() -> Result<()> {
         let g1 = G1::prime_subgroup_generator();
-        print_size(g1, "size of g1");
+        print_size(g1, "size of g1")?;
         let g2 = G2::prime_subgroup_generator();
         let gt = <I as PairingEngine>::pairing::<G1, G2>(
             g1.into_affine().into(),
             g2.into_affine().into(),
         );
-        print_size(g2, "size of g2");
-        print_size(gt, "size of GT");
-        print_size(Fr::one(), "size of Fr");
+        print_size(g2, "size of g2")?;
+        print_size(gt, "size of GT")?;
+        print_size(Fr::one(), "size of Fr")?;
         /*for (s, topic) in elements {*/
         /*print_size(s, topic).unwrap();*/
         /*}*/
+        Ok(())
     }
 
     fn print_size<S: CanonicalSerialize>(s: S, n: &str) -> Result<()> {
@@ -332,6 +692,199 @@ mod tests {
         }
     }
 
+    fn bench_nna_pair<F: PrimeField, CF: PrimeField>(pair: &str) {
+        let mut rng = ark_std::test_rng();
+        println!("{}", pair);
+        for mode in NNA_MODES {
+            let cs = ConstraintSystem::<CF>::new_ref();
+            NNACircuit::<F, CF>::rand(&mut rng, mode.clone())
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!("\tNNA Mode {:?} : {}", mode, cs.num_constraints());
+        }
+    }
+
+    #[test]
+    fn bench_nna_pairs() {
+        type Fq = <I as PairingEngine>::Fq;
+        bench_nna_pair::<ark_bls12_381::Fr, Fr>("BLS12-381 Fr over BLS12-377 Fr");
+        bench_nna_pair::<ark_bls12_381::Fq, Fr>("BLS12-381 Fq over BLS12-377 Fr");
+        bench_nna_pair::<Fr, Fq>("BLS12-377 Fr over BLS12-377 Fq");
+        bench_nna_pair::<ark_bls12_381::Fr, Fq>("BLS12-381 Fr over BLS12-377 Fq");
+        bench_nna_pair::<ark_bls12_381::Fq, Fq>("BLS12-381 Fq over BLS12-377 Fq");
+    }
+
+    /// Constraints and nonzero entries of A, B and C for a circuit
+    /// synthesized with the given optimization goal.
+    fn cost_with_goal<CF: PrimeField, C: ConstraintSynthesizer<CF>>(
+        circuit: C,
+        goal: OptimizationGoal,
+    ) -> (usize, usize) {
+        let cs = ConstraintSystem::<CF>::new_ref();
+        cs.set_optimization_goal(goal);
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        cs.finalize();
+        let m = cs.to_matrices().unwrap();
+        (
+            cs.num_constraints(),
+            m.a_num_non_zero + m.b_num_non_zero + m.c_num_non_zero,
+        )
+    }
+
+    #[test]
+    fn bench_nna_params() {
+        let mut rng = ark_std::test_rng();
+        type F381 = ark_bls12_381::Fr;
+        for (goal, opt) in [
+            (OptimizationGoal::Constraints, OptimizationType::Constraints),
+            (OptimizationGoal::Weight, OptimizationType::Weight),
+        ] {
+            let p = limbs::default_params::<F381, Fr>(opt);
+            println!(
+                "{:?} goal, {} limbs of {} bits",
+                goal, p.num_limbs, p.bits_per_limb
+            );
+            for mode in [NNAMode::Add, NNAMode::Mul] {
+                let (constraints, nonzeros) =
+                    cost_with_goal(NNACircuit::<F381, Fr>::rand(&mut rng, mode.clone()), goal);
+                println!(
+                    "\tNNA Mode {:?}: {} constraints, {} nonzeros",
+                    mode, constraints, nonzeros
+                );
+            }
+            for mode in [OpMode::NNAFieldAddOverFq, OpMode::NNAFieldMulOverFq] {
+                let circuit = FqCircuit::<I, IV>::new(
+                    &mut rng,
+                    mode.clone(),
+                    poseidon::get_bls12377_fq_params(2),
+                );
+                let (constraints, nonzeros) = cost_with_goal(circuit, goal);
+                println!(
+                    "\t{:?}: {} constraints, {} nonzeros",
+                    mode, constraints, nonzeros
+                );
+            }
+        }
+
+        // custom layouts for BLS12-381 Fr over BLS12-377 Fr, the defaults first
+        let custom = [
+            limbs::default_params::<F381, Fr>(OptimizationType::Constraints),
+            limbs::default_params::<F381, Fr>(OptimizationType::Weight),
+        ]
+        .into_iter()
+        .chain(
+            [(3, 85), (4, 64), (5, 51), (8, 32)].map(|(num_limbs, bits_per_limb)| {
+                NonNativeFieldParams {
+                    num_limbs,
+                    bits_per_limb,
+                }
+            }),
+        );
+        for params in custom {
+            let (constraints, nonzeros) = cost_with_goal(
+                NNACircuit::<F381, Fr>::with_limbs(&mut rng, NNAMode::Mul, params.clone()),
+                OptimizationGoal::Constraints,
+            );
+            println!(
+                "Custom limbs {} x {} bits, Mul: {} constraints, {} nonzeros",
+                params.num_limbs, params.bits_per_limb, constraints, nonzeros
+            );
+        }
+    }
+
+    #[test]
+    fn bench_groth16_verify() {
+        let mut rng = ark_std::test_rng();
+        for n in [1, 2, 4, 8, 16, 32] {
+            let cs = ConstraintSystem::<<I as PairingEngine>::Fq>::new_ref();
+            FqCircuit::<I, IV>::new(
+                &mut rng,
+                OpMode::Groth16Verify(n),
+                poseidon::get_bls12377_fq_params(2),
+            )
+            .generate_constraints(cs.clone())
+            .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!(
+                "groth16 verifier, {} public inputs: {} constraints, {} outer public inputs",
+                n,
+                cs.num_constraints(),
+                cs.num_instance_variables() - 1
+            );
+        }
+    }
+
+    #[test]
+    fn bench_nna_inner_product() {
+        let mut rng = ark_std::test_rng();
+        for n in [1, 4, 16, 64] {
+            let [lazy, naive] =
+                [OpMode::NNAInnerProduct(n), OpMode::NNAInnerProductNaive(n)].map(|mode| {
+                    let cs = ConstraintSystem::<<I as PairingEngine>::Fq>::new_ref();
+                    FqCircuit::<I, IV>::new(&mut rng, mode, poseidon::get_bls12377_fq_params(2))
+                        .generate_constraints(cs.clone())
+                        .unwrap();
+                    assert!(cs.is_satisfied().unwrap());
+                    cs.num_constraints()
+                });
+            println!(
+                "{} terms: {} constraints ({} per term) reduced once, {} ({} per term) reduced per product",
+                n,
+                lazy,
+                lazy / n,
+                naive,
+                naive / n
+            );
+        }
+    }
+
+    #[test]
+    fn bench_nna_hash_packing() {
+        let mut rng = ark_std::test_rng();
+        for n in [1, 3, 8, 16] {
+            let [limbs, packed] = [OpMode::NNAHash(n), OpMode::NNAHashPacked(n)].map(|mode| {
+                let cs = ConstraintSystem::<<I as PairingEngine>::Fq>::new_ref();
+                FqCircuit::<I, IV>::new(&mut rng, mode, poseidon::get_bls12377_fq_params(2))
+                    .generate_constraints(cs.clone())
+                    .unwrap();
+                assert!(cs.is_satisfied().unwrap());
+                cs.num_constraints()
+            });
+            println!(
+                "NNAHash({}): {} constraints absorbing limbs, {} packed, {} saved",
+                n,
+                limbs,
+                packed,
+                limbs - packed
+            );
+        }
+    }
+
+    #[test]
+    fn bench_nna_challenges() {
+        let mut rng = ark_std::test_rng();
+        for k in [1, 2, 4] {
+            for mode in [
+                OpMode::NNAChallenges(k),
+                OpMode::NNAShortChallenges(k),
+                OpMode::FqTruncatedChallenges(k),
+            ] {
+                let cs = ConstraintSystem::<<I as PairingEngine>::Fq>::new_ref();
+                FqCircuit::<I, IV>::new(
+                    &mut rng,
+                    mode.clone(),
+                    poseidon::get_bls12377_fq_params(2),
+                )
+                .generate_constraints(cs.clone())
+                .unwrap();
+                assert!(cs.is_satisfied().unwrap());
+                println!("{:?}: {} constraints", mode, cs.num_constraints());
+            }
+        }
+    }
+
     #[test]
     fn bench_bls12377() {
         let mut rng = ark_std::test_rng();
@@ -345,11 +898,38 @@ mod tests {
             OpMode::MillerLoop(45),
             OpMode::FinalExp,
             OpMode::Pairing,
+            OpMode::Groth16Verify(1),
+            OpMode::KzgVerify(1),
+            OpMode::KzgVerify(4),
+            OpMode::KzgVerify(16),
+            OpMode::GtLagrangeCombine(2),
+            OpMode::GtLagrangeCombine(4),
+            OpMode::GtLagrangeCombine(8),
+            OpMode::MerklePath(16, 2),
+            OpMode::MerklePath(32, 2),
+            OpMode::MerklePath(12, 4),
+            OpMode::MerklePath(8, 8),
             OpMode::G1Mul,
             OpMode::G2Mul,
+            OpMode::HashToG1,
+            OpMode::HashToG2,
             OpMode::NNAFieldAddOverFq,
             OpMode::NNAFieldMulOverFq,
             OpMode::NNAHash(3),
+            OpMode::NNAHashPacked(3),
+            OpMode::NNAChallenges(1),
+            OpMode::NNAShortChallenges(1),
+            OpMode::FqTruncatedChallenges(1),
+            OpMode::PedersenCommit(1),
+            OpMode::PedersenCommit(4),
+            OpMode::NNAPolyEval(4),
+            OpMode::NNAPolyEval(16),
+            OpMode::NNAShamirReconstruct(4),
+            OpMode::NNAShamirReconstruct(16),
+            OpMode::NNAInnerProduct(4),
+            OpMode::NNAInnerProduct(16),
+            OpMode::NNAInnerProductNaive(4),
+            OpMode::NNAInnerProductNaive(16),
         ] {
             println!("GT operation {:?}", mode);
             let cs = ConstraintSystem::<<I as PairingEngine>::Fq>::new_ref();
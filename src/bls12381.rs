@@ -1,23 +1,49 @@
+use super::merkle::{self, MerklePath, MerklePathVar};
+use super::packing;
 use super::OpMode;
 use ark_ec::PairingEngine;
 use ark_ff::PrimeField;
 use ark_nonnative_field::NonNativeFieldVar;
 use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::fields::fp::FpVar;
 use ark_r1cs_std::ToConstraintFieldGadget;
 use ark_relations::r1cs::ConstraintSynthesizer;
 use ark_sponge::constraints::CryptographicSpongeVar;
 use ark_sponge::poseidon::constraints::PoseidonSpongeVar;
 use ark_sponge::poseidon::PoseidonParameters;
-use ark_std::UniformRand;
-use std::{marker::PhantomData, os::unix::thread};
+use ark_sponge::Absorb;
+use ark_std::rand::Rng;
+use std::marker::PhantomData;
+#[allow(dead_code)]
 struct Circuit<E: PairingEngine, NNA: PrimeField> {
     mode: OpMode,
     p: PoseidonParameters<E::Fr>,
+    nna: NNA,
+    merkle: Option<(E::Fr, MerklePath<E::Fr>)>,
     _p: PhantomData<E>,
-    _f: PhantomData<NNA>,
 }
 
-impl<E: PairingEngine, NNA: PrimeField> ConstraintSynthesizer<E::Fr> for Circuit<E, NNA> {
+#[allow(dead_code)]
+impl<E: PairingEngine, NNA: PrimeField> Circuit<E, NNA> {
+    fn new<R: Rng>(rng: &mut R, mode: OpMode, p: PoseidonParameters<E::Fr>) -> Self {
+        let merkle = match mode {
+            OpMode::MerklePath(depth, arity) => Some(merkle::random_path(rng, arity, depth)),
+            _ => None,
+        };
+        Self {
+            mode,
+            p,
+            nna: NNA::rand(rng),
+            merkle,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<E: PairingEngine, NNA: PrimeField> ConstraintSynthesizer<E::Fr> for Circuit<E, NNA>
+where
+    E::Fr: Absorb,
+{
     fn generate_constraints(
         self,
         cs: ark_relations::r1cs::ConstraintSystemRef<E::Fr>,
@@ -27,16 +53,68 @@ impl<E: PairingEngine, NNA: PrimeField> ConstraintSynthesizer<E::Fr> for Circuit
                 let mut sponge = PoseidonSpongeVar::new(cs.clone(), &self.p);
                 let cv = NonNativeFieldVar::<NNA, E::Fr>::new_witness(
                     ark_relations::ns!(cs, "nna hash"),
-                    || Ok(NNA::rand(&mut rand::thread_rng())),
+                    || Ok(self.nna),
                 )
                 .unwrap();
-                for i in 0..n {
-                    sponge.absorb(&cv.to_constraint_field()?);
+                for _ in 0..n {
+                    sponge.absorb(&cv.to_constraint_field()?)?;
                 }
-                sponge.squeeze_nonnative_field_elements::<E::Fr>(1);
+                sponge.squeeze_nonnative_field_elements::<E::Fr>(1)?;
+            }
+            OpMode::NNAHashPacked(n) => {
+                let mut sponge = PoseidonSpongeVar::new(cs.clone(), &self.p);
+                let cv = NonNativeFieldVar::<NNA, E::Fr>::new_witness(
+                    ark_relations::ns!(cs, "nna hash"),
+                    || Ok(self.nna),
+                )?;
+                let bits = packing::bits_var(&cv)?;
+                let bits = (0..n).flat_map(|_| bits.clone()).collect::<Vec<_>>();
+                sponge.absorb(&packing::pack_bits_var(&bits)?)?;
+                sponge.squeeze_nonnative_field_elements::<E::Fr>(1)?;
+            }
+            OpMode::MerklePath(..) => {
+                let (leaf, path) = self.merkle.unwrap();
+                let root = path.root(&self.p, &leaf);
+                let root = FpVar::new_input(ark_relations::ns!(cs, "root"), || Ok(root))?;
+                let leaf = FpVar::new_witness(ark_relations::ns!(cs, "leaf"), || Ok(leaf))?;
+                MerklePathVar::new_witness(cs.clone(), &path)?
+                    .enforce_membership(&self.p, &root, &leaf)?;
             }
             _ => panic!("unsupported mode"),
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{Bls12_377, Fr};
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn bench_bls12377_fr() {
+        let mut rng = ark_std::test_rng();
+        for mode in [
+            OpMode::MerklePath(16, 2),
+            OpMode::MerklePath(32, 2),
+            OpMode::MerklePath(12, 4),
+            OpMode::MerklePath(8, 8),
+            OpMode::NNAHash(3),
+            OpMode::NNAHashPacked(3),
+        ] {
+            println!("Fr operation {:?}", mode);
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            Circuit::<Bls12_377, ark_bls12_381::Fr>::new(
+                &mut rng,
+                mode,
+                poseidon::get_bls12377_fr_params(),
+            )
+            .generate_constraints(cs.clone())
+            .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!("\t-Num constraints: {}", cs.num_constraints());
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Chaum-Pedersen proofs of equal discrete logarithms across G1 and GT, as
+//! given by each party of the threshold decryption: log_g(pk_i) ==
+//! log_C1(D_i), with g the G1 generator, C1 the first component of an
+//! ElGamal ciphertext and D_i = C1^x_i the decryption share.
+//!
+//! A proof is (c, z) with A = g^k, B = C1^k, c = H(pk_i, C1, D_i, A, B) and
+//! z = k - c x_i; the verifier recomputes A = g^z pk_i^c and B = C1^z D_i^c
+//! and checks the challenge. The transcript is Poseidon over BLS12-377 Fq.
+use super::elgamal::alloc_scalar_bits;
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{BigInteger, Field, PrimeField, ToConstraintField};
+use ark_r1cs_std::{
+    alloc::AllocVar, bits::boolean::Boolean, eq::EqGadget, fields::FieldVar, groups::CurveVar,
+    pairing::PairingVar, ToBitsGadget, ToConstraintFieldGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters, PoseidonSponge},
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_std::{
+    marker::PhantomData,
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+
+/// Size in bits of the Fiat-Shamir challenge.
+const CHALLENGE_BITS: usize = 128;
+
+pub struct DleqProof<I: PairingEngine> {
+    pub c: I::Fr,
+    pub z: I::Fr,
+}
+
+impl<I: PairingEngine> Clone for DleqProof<I> {
+    fn clone(&self) -> Self {
+        Self {
+            c: self.c,
+            z: self.z,
+        }
+    }
+}
+
+/// The public statement of one party: its key pk_i in G1 and its decryption
+/// share D_i in GT.
+pub struct Share<I: PairingEngine> {
+    pub pk: I::G1Affine,
+    pub d: I::Fqk,
+}
+
+impl<I: PairingEngine> Clone for Share<I> {
+    fn clone(&self) -> Self {
+        Self {
+            pk: self.pk,
+            d: self.d,
+        }
+    }
+}
+
+/// Returns the share of `x` for the ciphertext component `c1`.
+pub fn share<I: PairingEngine>(x: &I::Fr, c1: &I::Fqk) -> Share<I> {
+    Share {
+        pk: I::G1Affine::prime_subgroup_generator()
+            .mul(*x)
+            .into_affine(),
+        d: c1.pow(x.into_repr()),
+    }
+}
+
+/// The challenge is the low `CHALLENGE_BITS` bits of a squeezed base field
+/// element.
+fn challenge<I>(
+    params: &PoseidonParameters<I::Fq>,
+    c1: &I::Fqk,
+    share: &Share<I>,
+    a: &I::G1Affine,
+    b: &I::Fqk,
+) -> I::Fr
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    let mut sponge = PoseidonSponge::new(params);
+    sponge.absorb(&share.pk.to_field_elements().unwrap());
+    sponge.absorb(&c1.to_field_elements().unwrap());
+    sponge.absorb(&share.d.to_field_elements().unwrap());
+    sponge.absorb(&a.to_field_elements().unwrap());
+    sponge.absorb(&b.to_field_elements().unwrap());
+    let bits = sponge.squeeze_native_field_elements(1)[0]
+        .into_repr()
+        .to_bits_le();
+    I::Fr::from_repr(<I::Fr as PrimeField>::BigInt::from_bits_le(
+        &bits[..CHALLENGE_BITS],
+    ))
+    .unwrap()
+}
+
+#[allow(dead_code)]
+pub fn prove<I, R>(
+    rng: &mut R,
+    params: &PoseidonParameters<I::Fq>,
+    x: &I::Fr,
+    c1: &I::Fqk,
+) -> DleqProof<I>
+where
+    I: PairingEngine,
+    R: Rng + CryptoRng,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    let k = I::Fr::rand(rng);
+    let a = I::G1Affine::prime_subgroup_generator().mul(k).into_affine();
+    let b = c1.pow(k.into_repr());
+    let c = challenge(params, c1, &share::<I>(x, c1), &a, &b);
+    DleqProof { c, z: k - c * x }
+}
+
+#[allow(dead_code)]
+pub fn verify<I>(
+    params: &PoseidonParameters<I::Fq>,
+    c1: &I::Fqk,
+    share: &Share<I>,
+    proof: &DleqProof<I>,
+) -> bool
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    let a = I::G1Affine::prime_subgroup_generator().mul(proof.z) + share.pk.mul(proof.c);
+    let b = c1.pow(proof.z.into_repr()) * share.d.pow(proof.c.into_repr());
+    challenge(params, c1, share, &a.into_affine(), &b) == proof.c
+}
+
+/// Proof variables: the bits of the challenge and of the response.
+pub struct DleqProofVar<I: PairingEngine> {
+    c: Vec<Boolean<I::Fq>>,
+    z: Vec<Boolean<I::Fq>>,
+}
+
+impl<I: PairingEngine> DleqProofVar<I> {
+    pub fn new_witness(
+        cs: ConstraintSystemRef<I::Fq>,
+        proof: &DleqProof<I>,
+    ) -> Result<Self, SynthesisError> {
+        let c = Vec::<Boolean<I::Fq>>::new_witness(ns!(cs, "c"), || {
+            Ok(proof.c.into_repr().to_bits_le()[..CHALLENGE_BITS].to_vec())
+        })?;
+        let z = alloc_scalar_bits::<I>(cs, Some(proof.z))?;
+        Ok(Self { c, z })
+    }
+}
+
+/// In-circuit version of [`verify`].
+pub fn enforce_dleq_verify<I, IV>(
+    cs: ConstraintSystemRef<I::Fq>,
+    params: &PoseidonParameters<I::Fq>,
+    c1: &IV::GTVar,
+    pk: &IV::G1Var,
+    d: &IV::GTVar,
+    proof: &DleqProofVar<I>,
+) -> Result<(), SynthesisError>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+    IV::G1Var: ToConstraintFieldGadget<I::Fq>,
+    IV::GTVar: ToConstraintFieldGadget<I::Fq>,
+{
+    let g = IV::G1Var::constant(I::G1Projective::prime_subgroup_generator());
+    let a = g.scalar_mul_le(proof.z.iter())? + pk.scalar_mul_le(proof.c.iter())?;
+    let b = c1.pow_le(&proof.z)? * d.pow_le(&proof.c)?;
+
+    let mut sponge = PoseidonSpongeVar::new(cs, params);
+    sponge.absorb(&pk.to_constraint_field()?)?;
+    sponge.absorb(&c1.to_constraint_field()?)?;
+    sponge.absorb(&d.to_constraint_field()?)?;
+    sponge.absorb(&a.to_constraint_field()?)?;
+    sponge.absorb(&b.to_constraint_field()?)?;
+    let c = sponge.squeeze_field_elements(1)?.remove(0).to_bits_le()?;
+    c[..CHALLENGE_BITS].enforce_equal(&proof.c)
+}
+
+/// Ciphertext component and share variables of the batch circuit.
+type DleqInputs<I, IV> = (
+    <IV as PairingVar<I>>::GTVar,
+    Vec<(<IV as PairingVar<I>>::G1Var, <IV as PairingVar<I>>::GTVar)>,
+);
+
+/// Verifies the DLEQ proofs of a batch of decryption shares for one public
+/// ciphertext component C1; the keys and shares are public inputs.
+pub struct DleqCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    c1: I::Fqk,
+    shares: Vec<Share<I>>,
+    proofs: Vec<DleqProof<I>>,
+    poseidon_params: PoseidonParameters<I::Fq>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> DleqCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(
+        c1: I::Fqk,
+        shares: Vec<Share<I>>,
+        proofs: Vec<DleqProof<I>>,
+        params: PoseidonParameters<I::Fq>,
+    ) -> Self {
+        assert_eq!(shares.len(), proofs.len());
+        Self {
+            c1,
+            shares,
+            proofs,
+            poseidon_params: params,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        c1: I::Fqk,
+        shares: &[Share<I>],
+    ) -> Result<DleqInputs<I, IV>, SynthesisError> {
+        let c1 = IV::GTVar::new_input(ns!(cs, "c1"), || Ok(c1))?;
+        let shares = shares
+            .iter()
+            .map(|s| {
+                let pk = IV::G1Var::new_input(ns!(cs, "pk"), || Ok(s.pk.into_projective()))?;
+                let d = IV::GTVar::new_input(ns!(cs, "d"), || Ok(s.d))?;
+                Ok((pk, d))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+        Ok((c1, shares))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(c1: I::Fqk, shares: &[Share<I>]) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), c1, shares)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for DleqCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+    IV::G1Var: ToConstraintFieldGadget<I::Fq>,
+    IV::GTVar: ToConstraintFieldGadget<I::Fq>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let (c1, shares) = Self::alloc_inputs(cs.clone(), self.c1, &self.shares)?;
+        for ((pk, d), proof) in shares.iter().zip(&self.proofs) {
+            let proof = DleqProofVar::new_witness(cs.clone(), proof)?;
+            enforce_dleq_verify::<I, IV>(cs.clone(), &self.poseidon_params, &c1, pk, d, &proof)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{elgamal, poseidon};
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq, Fr};
+
+    type Circuit = DleqCircuit<I, IV>;
+
+    /// An ElGamal C1 with `n` key shares and their proofs.
+    fn shares_and_proofs(
+        params: &PoseidonParameters<Fq>,
+        n: usize,
+    ) -> (<I as PairingEngine>::Fqk, Vec<Share<I>>, Vec<DleqProof<I>>) {
+        let mut rng = ark_std::test_rng();
+        let c1 = elgamal::generator::<I>().pow(Fr::rand(&mut rng).into_repr());
+        let xs = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let shares = xs.iter().map(|x| share::<I>(x, &c1)).collect();
+        let proofs = xs
+            .iter()
+            .map(|x| prove::<I, _>(&mut rng, params, x, &c1))
+            .collect();
+        (c1, shares, proofs)
+    }
+
+    #[test]
+    fn native_dleq() {
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (c1, shares, proofs) = shares_and_proofs(&params, 2);
+        assert!(verify::<I>(&params, &c1, &shares[0], &proofs[0]));
+        assert!(verify::<I>(&params, &c1, &shares[1], &proofs[1]));
+        assert!(!verify::<I>(&params, &c1, &shares[0], &proofs[1]));
+        // a share for another key
+        let wrong = Share::<I> {
+            pk: shares[0].pk,
+            d: shares[1].d,
+        };
+        assert!(!verify::<I>(&params, &c1, &wrong, &proofs[0]));
+    }
+
+    #[test]
+    fn dleq_circuit() {
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (c1, shares, proofs) = shares_and_proofs(&params, 2);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(c1, shares.clone(), proofs.clone(), params.clone())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(
+            Circuit::public_inputs(c1, &shares).unwrap().len(),
+            cs.num_instance_variables() - 1
+        );
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let swapped = vec![proofs[1].clone(), proofs[0].clone()];
+        Circuit::new(c1, shares, swapped, params)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn bench_dleq() {
+        let params = poseidon::get_bls12377_fq_params(2);
+        for n in [1, 2, 4, 8] {
+            let (c1, shares, proofs) = shares_and_proofs(&params, n);
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            Circuit::new(c1, shares, proofs, params.clone())
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!(
+                "DLEQ batch of {}: {} constraints, {} per share",
+                n,
+                cs.num_constraints(),
+                cs.num_constraints() / n
+            );
+        }
+    }
+}
@@ -0,0 +1,635 @@
+//! drand-style randomness beacon over BLS12-381: the group key is in G1 and
+//! the round signatures in G2, and the circuit verifying a round runs inside
+//! a BLS12-377-family constraint field with BLS12-381 emulated.
+//!
+//! In chained mode round n signs H(n || sig_{n-1}), in unchained mode H(n).
+//! Messages and the round randomness are hashed with Poseidon over the
+//! constraint field in place of SHA-256, so signatures are not compatible
+//! with the public drand network, only its structure is.
+use super::emulated::{
+    curves::{G1AffineVar, G2AffineVar},
+    fields::Fq12Var,
+    hash_to_curve::{enforce_in_g2, hash_to_g2, hash_to_g2_var},
+    pairing::{final_exponentiation, miller_loop},
+};
+use super::hash_to_curve::{hash_to_field, hash_to_field_var, Sgn0, Sgn0Gadget};
+use ark_bls12_381::{Bls12_381, Fq, Fr, G1Affine, G2Affine};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{BigInteger, One, PrimeField};
+use ark_r1cs_std::{
+    alloc::AllocVar, bits::boolean::Boolean, eq::EqGadget, fields::fp::FpVar, ToBitsGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::{poseidon::PoseidonParameters, Absorb};
+use ark_std::{
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+use std::ops::Neg;
+
+pub const RANDOMNESS_DOMAIN: &[u8] = b"DRAND_RANDOMNESS";
+
+#[allow(dead_code)]
+pub fn keygen<R: Rng + CryptoRng>(rng: &mut R) -> (Fr, G1Affine) {
+    let sk = Fr::rand(rng);
+    let pk = G1Affine::prime_subgroup_generator().mul(sk).into_affine();
+    (sk, pk)
+}
+
+/// Bits of a signature in compressed form: x.c0, x.c1 and sgn0(y).
+fn signature_bits(sig: &G2Affine) -> Vec<bool> {
+    let coord = |c: Fq| c.into_repr().to_bits_le()[..Fq::size_in_bits()].to_vec();
+    let mut bits = coord(sig.x.c0);
+    bits.extend(coord(sig.x.c1));
+    bits.push(sig.y.sgn0());
+    bits
+}
+
+/// Packs the compressed signature into constraint field elements of
+/// |CF| - 1 bits each, least significant first.
+pub fn pack_signature<CF: PrimeField>(sig: &G2Affine) -> Vec<CF> {
+    signature_bits(sig)
+        .chunks(CF::size_in_bits() - 1)
+        .map(|c| CF::from_repr(BigInteger::from_bits_le(c)).unwrap())
+        .collect()
+}
+
+/// In-circuit [`pack_signature`]; the bits of the coordinates are canonical.
+pub fn pack_signature_var<CF: PrimeField>(
+    sig: &G2AffineVar<CF>,
+) -> Result<Vec<FpVar<CF>>, SynthesisError> {
+    let n = Fq::size_in_bits();
+    let mut bits = sig.x.c0.to_bits_le()?[..n].to_vec();
+    bits.extend_from_slice(&sig.x.c1.to_bits_le()?[..n]);
+    bits.push(sig.y.sgn0()?);
+    bits.chunks(CF::size_in_bits() - 1)
+        .map(Boolean::le_bits_to_fp_var)
+        .collect()
+}
+
+/// The signed message: the round number, followed by the packed previous
+/// signature in chained mode.
+pub fn round_message<CF: PrimeField>(round: u64, prev: Option<&G2Affine>) -> Vec<CF> {
+    let mut msg = vec![CF::from(round)];
+    if let Some(prev) = prev {
+        msg.extend(pack_signature::<CF>(prev));
+    }
+    msg
+}
+
+pub fn sign<CF: PrimeField + Absorb>(
+    params: &PoseidonParameters<CF>,
+    sk: &Fr,
+    round: u64,
+    prev: Option<&G2Affine>,
+) -> G2Affine {
+    hash_to_g2(params, &round_message(round, prev))
+        .mul(*sk)
+        .into_affine()
+}
+
+/// Checks that `sig` is in G2 and e(g1, sig) == e(pk, H(m)) with a single
+/// final exponentiation.
+#[allow(dead_code)]
+pub fn verify<CF: PrimeField + Absorb>(
+    params: &PoseidonParameters<CF>,
+    pk: &G1Affine,
+    round: u64,
+    prev: Option<&G2Affine>,
+    sig: &G2Affine,
+) -> bool {
+    if !sig.is_in_correct_subgroup_assuming_on_curve() {
+        return false;
+    }
+    let h = hash_to_g2(params, &round_message(round, prev));
+    Bls12_381::product_of_pairings(&[
+        (
+            G1Affine::prime_subgroup_generator().neg().into(),
+            (*sig).into(),
+        ),
+        ((*pk).into(), h.into()),
+    ])
+    .is_one()
+}
+
+/// The beacon output of a round, derived from its signature.
+pub fn randomness<CF: PrimeField + Absorb>(params: &PoseidonParameters<CF>, sig: &G2Affine) -> CF {
+    hash_to_field(params, RANDOMNESS_DOMAIN, &pack_signature(sig), 1)[0]
+}
+
+#[derive(Clone, Debug)]
+pub struct Beacon {
+    pub round: u64,
+    pub previous: Option<G2Affine>,
+    pub signature: G2Affine,
+}
+
+/// Signs rounds 1 to `rounds`; chained from `genesis` if given, unchained
+/// otherwise.
+#[allow(dead_code)]
+pub fn beacon_chain<CF: PrimeField + Absorb>(
+    params: &PoseidonParameters<CF>,
+    sk: &Fr,
+    genesis: Option<G2Affine>,
+    rounds: u64,
+) -> Vec<Beacon> {
+    let mut prev = genesis;
+    (1..=rounds)
+        .map(|round| {
+            let signature = sign(params, sk, round, prev.as_ref());
+            let beacon = Beacon {
+                round,
+                previous: prev,
+                signature,
+            };
+            if prev.is_some() {
+                prev = Some(signature);
+            }
+            beacon
+        })
+        .collect()
+}
+
+/// Round number, packed previous signature (empty when unchained) and
+/// randomness variables of the circuit.
+type DrandInputs<CF> = (FpVar<CF>, Vec<FpVar<CF>>, FpVar<CF>);
+
+/// Verifies one beacon round for a group key fixed in the circuit. The round,
+/// the previous signature in chained mode and the randomness are public
+/// inputs; the signature is a witness checked to be in G2, so that each round
+/// has a single valid signature and hence a single randomness.
+pub struct DrandCircuit<CF: PrimeField> {
+    pk: G1Affine,
+    beacon: Beacon,
+    poseidon_params: PoseidonParameters<CF>,
+}
+
+impl<CF: PrimeField + Absorb> DrandCircuit<CF> {
+    #[allow(dead_code)]
+    pub fn new(pk: G1Affine, beacon: Beacon, params: PoseidonParameters<CF>) -> Self {
+        Self {
+            pk,
+            beacon,
+            poseidon_params: params,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<CF>,
+        params: &PoseidonParameters<CF>,
+        beacon: &Beacon,
+    ) -> Result<DrandInputs<CF>, SynthesisError> {
+        let round = FpVar::new_input(ns!(cs, "round"), || Ok(CF::from(beacon.round)))?;
+        let prev = beacon
+            .previous
+            .as_ref()
+            .map(pack_signature::<CF>)
+            .unwrap_or_default();
+        let prev = Vec::<FpVar<CF>>::new_input(ns!(cs, "previous"), || Ok(prev))?;
+        let randomness = FpVar::new_input(ns!(cs, "randomness"), || {
+            Ok(randomness(params, &beacon.signature))
+        })?;
+        Ok((round, prev, randomness))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(
+        params: &PoseidonParameters<CF>,
+        beacon: &Beacon,
+    ) -> Result<Vec<CF>, SynthesisError> {
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), params, beacon)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<CF: PrimeField + Absorb> ConstraintSynthesizer<CF> for DrandCircuit<CF> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<CF>) -> Result<(), SynthesisError> {
+        let params = &self.poseidon_params;
+        let (round, prev, randomness) = Self::alloc_inputs(cs.clone(), params, &self.beacon)?;
+        let sig = G2AffineVar::new_witness(ns!(cs, "sig"), || Ok(self.beacon.signature))?;
+        enforce_in_g2(&sig)?;
+
+        let mut msg = vec![round];
+        msg.extend(prev);
+        let h = hash_to_g2_var(params, &msg)?;
+        let g1 = G1AffineVar::constant(G1Affine::prime_subgroup_generator().neg());
+        let pk = G1AffineVar::constant(self.pk);
+        let ml = miller_loop(&[g1, pk], &[sig.clone(), h])?;
+        final_exponentiation(&ml)?.enforce_equal(&Fq12Var::one())?;
+
+        let packed = pack_signature_var(&sig)?;
+        hash_to_field_var(params, RANDOMNESS_DOMAIN, &packed, 1)?[0].enforce_equal(&randomness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulated::{
+        fields::{Fq2Var, FqVar},
+        hash_to_curve::{
+            hash_to_fq2_var, map_to_curve, map_to_curve_var, psi_var, small_order_point,
+        },
+        pairing::miller_loop_bits,
+    };
+    use crate::hash_to_curve::clear_cofactor_g2;
+    use crate::poseidon::get_bls12377_fq_params;
+    use ark_bls12_381::{Fq12, Fq2, G1Projective, G2Projective, Parameters};
+    use ark_ec::bls12::Bls12Parameters;
+    use ark_ff::BitIteratorBE;
+    use ark_nonnative_field::{AllocatedNonNativeFieldVar, NonNativeFieldVar};
+    use ark_r1cs_std::R1CSVar;
+    use ark_relations::r1cs::SynthesisMode;
+    use std::{cell::RefCell, marker::PhantomData};
+
+    type CF = ark_bls12_377::Fq;
+
+    #[test]
+    fn native_beacon() {
+        let mut rng = ark_std::test_rng();
+        let params = get_bls12377_fq_params(2);
+        let (sk, pk) = keygen(&mut rng);
+        let genesis = G2Projective::rand(&mut rng).into_affine();
+        for chain in [
+            beacon_chain::<CF>(&params, &sk, Some(genesis), 3),
+            beacon_chain::<CF>(&params, &sk, None, 3),
+        ] {
+            for b in &chain {
+                assert!(verify(
+                    &params,
+                    &pk,
+                    b.round,
+                    b.previous.as_ref(),
+                    &b.signature
+                ));
+                assert!(!verify(
+                    &params,
+                    &pk,
+                    b.round + 1,
+                    b.previous.as_ref(),
+                    &b.signature
+                ));
+            }
+            let chained = chain[0].previous.is_some();
+            for w in chain.windows(2) {
+                assert_eq!(w[1].previous.is_some(), chained);
+                if chained {
+                    assert_eq!(w[1].previous, Some(w[0].signature));
+                    // the previous signature is part of the message
+                    assert!(!verify(&params, &pk, w[1].round, None, &w[1].signature));
+                }
+            }
+            println!(
+                "round {} randomness ({}): {}",
+                chain[2].round,
+                if chained { "chained" } else { "unchained" },
+                randomness(&params, &chain[2].signature)
+            );
+        }
+
+        // a signature moved out of G2 by a point of small order
+        let b = &beacon_chain::<CF>(&params, &sk, None, 1)[0];
+        let forged =
+            (b.signature.into_projective() + small_order_point().into_projective()).into_affine();
+        assert!(!verify(&params, &pk, b.round, None, &forged));
+
+        let packed = pack_signature::<CF>(&genesis);
+        assert_eq!(packed.len(), 3);
+        assert_ne!(packed, pack_signature::<CF>(&genesis.neg()));
+    }
+
+    #[test]
+    fn drand_gadgets() {
+        let mut rng = ark_std::test_rng();
+        let params = get_bls12377_fq_params(2);
+        let sig = G2Projective::rand(&mut rng).into_affine();
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let sig_var = G2AffineVar::new_witness(ns!(cs, "sig"), || Ok(sig)).unwrap();
+        let packed = pack_signature_var(&sig_var).unwrap();
+        assert_eq!(packed.value().unwrap(), pack_signature::<CF>(&sig));
+        let r = hash_to_field_var(&params, RANDOMNESS_DOMAIN, &packed, 1).unwrap();
+        assert_eq!(r[0].value().unwrap(), randomness(&params, &sig));
+        assert!(cs.is_satisfied().unwrap());
+
+        let beacon = Beacon {
+            round: 5,
+            previous: Some(sig),
+            signature: sig,
+        };
+        let inputs = DrandCircuit::<CF>::public_inputs(&params, &beacon).unwrap();
+        assert_eq!(inputs.len(), 5);
+        let unchained = Beacon {
+            previous: None,
+            ..beacon
+        };
+        assert_eq!(
+            DrandCircuit::<CF>::public_inputs(&params, &unchained)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    /// The round message hashed to G2 in circuit, cofactor clearing
+    /// included, against the native hash. Its matrices do not fit in memory,
+    /// so only the value is checked, and even then it needs far more memory
+    /// than the other tests.
+    #[test]
+    #[ignore]
+    fn round_hash() {
+        let mut rng = ark_std::test_rng();
+        let params = get_bls12377_fq_params(2);
+        let prev = G2Projective::rand(&mut rng).into_affine();
+        for msg in [
+            round_message::<CF>(3, Some(&prev)),
+            round_message::<CF>(3, None),
+        ] {
+            let cs = ConstraintSystem::<CF>::new_ref();
+            cs.set_mode(SynthesisMode::Prove {
+                construct_matrices: false,
+            });
+            let msg_var = Vec::<FpVar<CF>>::new_input(ns!(cs, "msg"), || Ok(msg.clone())).unwrap();
+            let h = hash_to_g2_var(&params, &msg_var).unwrap();
+            assert_eq!(h.value().unwrap(), hash_to_g2(&params, &msg));
+            println!("round message to G2: {} constraints", cs.num_constraints());
+        }
+    }
+
+    /// Constraints added by `op` on a fresh constraint system, which is
+    /// dropped before the next measurement to keep the memory bounded.
+    fn count(op: impl FnOnce(ConstraintSystemRef<CF>)) -> usize {
+        let cs = ConstraintSystem::<CF>::new_ref();
+        op(cs.clone());
+        assert!(cs.is_satisfied().unwrap());
+        cs.num_constraints()
+    }
+
+    fn fq12_op_cost(op: impl FnOnce(&Fq12Var<CF>)) -> usize {
+        let a = Fq12::rand(&mut ark_std::test_rng());
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let av = Fq12Var::new_witness(ns!(cs, "a"), || Ok(a)).unwrap();
+        let before = cs.num_constraints();
+        op(&av);
+        assert!(cs.is_satisfied().unwrap());
+        cs.num_constraints() - before
+    }
+
+    /// `x` allocated in `cs` with the same limbs and addition counters, so
+    /// that the next operation on it costs what it would in the original
+    /// constraint system.
+    fn rebase_fq(cs: &ConstraintSystemRef<CF>, x: &FqVar<CF>) -> FqVar<CF> {
+        match x {
+            NonNativeFieldVar::Constant(_) => x.clone(),
+            NonNativeFieldVar::Var(x) => NonNativeFieldVar::Var(AllocatedNonNativeFieldVar {
+                cs: cs.clone(),
+                limbs: x
+                    .limbs
+                    .iter()
+                    .map(|l| FpVar::new_witness(ns!(cs, "limb"), || l.value()).unwrap())
+                    .collect(),
+                num_of_additions_over_normal_form: x.num_of_additions_over_normal_form,
+                is_in_the_normal_form: x.is_in_the_normal_form,
+                target_phantom: PhantomData,
+            }),
+        }
+    }
+
+    fn rebase_fq2(cs: &ConstraintSystemRef<CF>, x: &Fq2Var<CF>) -> Fq2Var<CF> {
+        Fq2Var::new(rebase_fq(cs, &x.c0), rebase_fq(cs, &x.c1))
+    }
+
+    /// Applies `op` to copies of `ps` in a fresh constraint system, adding
+    /// its constraints to `cost`: long chains of G2 operations cost more
+    /// per step as the counters of the coordinates grow, but their matrices
+    /// do not fit in memory.
+    fn g2_step(
+        ps: &[&G2AffineVar<CF>],
+        cost: &mut usize,
+        op: impl FnOnce(&[G2AffineVar<CF>]) -> G2AffineVar<CF>,
+    ) -> G2AffineVar<CF> {
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let ps = ps
+            .iter()
+            .map(|p| G2AffineVar::new(rebase_fq2(&cs, &p.x), rebase_fq2(&cs, &p.y)))
+            .collect::<Vec<_>>();
+        let res = op(&ps);
+        assert!(cs.is_satisfied().unwrap());
+        *cost += cs.num_constraints();
+        res
+    }
+
+    /// [x]P as in `mul_by_x_var`, one step at a time.
+    fn g2_mul_by_x(p: &G2AffineVar<CF>, cost: &mut usize) -> G2AffineVar<CF> {
+        let mut bits = BitIteratorBE::without_leading_zeros(Parameters::X);
+        assert_eq!(bits.next(), Some(true));
+        let mut res = p.clone();
+        for bit in bits {
+            res = g2_step(&[&res], cost, |v| v[0].double().unwrap());
+            if bit {
+                res = g2_step(&[&res, p], cost, |v| v[0].add_distinct(&v[1]).unwrap());
+            }
+        }
+        if Parameters::X_IS_NEGATIVE {
+            g2_step(&[&res], cost, |v| v[0].negate().unwrap())
+        } else {
+            res
+        }
+    }
+
+    /// Per-block costs of a round, which unlike [`bench_drand`] fit in the
+    /// memory of a default test run: exact counts for the hashing, the G2
+    /// membership check and the cofactor clearing, and estimates of the
+    /// pairing check assembled from measured Miller loop steps and Fq12
+    /// operations.
+    #[test]
+    fn bench_drand_blocks() {
+        let mut rng = ark_std::test_rng();
+        let params = get_bls12377_fq_params(2);
+        let sig = G2Projective::rand(&mut rng).into_affine();
+
+        let mut hash_costs = vec![];
+        for (name, prev) in [("chained", Some(sig)), ("unchained", None)] {
+            let msg = round_message::<CF>(1, prev.as_ref());
+            let cost = count(|cs| {
+                let msg = Vec::<FpVar<CF>>::new_input(ns!(cs, "msg"), || Ok(msg)).unwrap();
+                let u = hash_to_fq2_var(&params, &msg, 2).unwrap();
+                map_to_curve_var(&u[0])
+                    .unwrap()
+                    .add(&map_to_curve_var(&u[1]).unwrap())
+                    .unwrap();
+            });
+            println!("{} message to curve: {} constraints", name, cost);
+            hash_costs.push((name, cost));
+        }
+        let output = count(|cs| {
+            let sig = G2AffineVar::new_witness(ns!(cs, "sig"), || Ok(sig)).unwrap();
+            let packed = pack_signature_var(&sig).unwrap();
+            hash_to_field_var(&params, RANDOMNESS_DOMAIN, &packed, 1).unwrap();
+        });
+        println!(
+            "signature allocation and randomness: {} constraints",
+            output
+        );
+
+        // the membership check and the cofactor clearing, step by step
+        let p = G2Projective::rand(&mut rng).into_affine();
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let pv = G2AffineVar::new_witness(ns!(cs, "p"), || Ok(p)).unwrap();
+        let mut membership = 0;
+        let x_p = g2_mul_by_x(&pv, &mut membership);
+        let psi_p = g2_step(&[&pv], &mut membership, |v| psi_var(&v[0]).unwrap());
+        g2_step(&[&psi_p, &x_p], &mut membership, |v| {
+            v[0].enforce_equal(&v[1]).unwrap();
+            v[0].clone()
+        });
+        println!("G2 membership of the signature: {} constraints", membership);
+
+        // on a point of the curve outside G2, as the map to the curve gives
+        let p = map_to_curve(Fq2::rand(&mut rng));
+        let pv = G2AffineVar::new_witness(ns!(cs, "p"), || Ok(p)).unwrap();
+        let mut cofactor = 0;
+        let x_p = g2_mul_by_x(&pv, &mut cofactor);
+        let psi_p = g2_step(&[&pv], &mut cofactor, |v| psi_var(&v[0]).unwrap());
+        let psi2_2p = g2_step(&[&pv], &mut cofactor, |v| {
+            psi_var(&psi_var(&v[0].double().unwrap()).unwrap()).unwrap()
+        });
+        let sum = g2_step(&[&x_p, &psi_p], &mut cofactor, |v| v[0].add(&v[1]).unwrap());
+        let x2_p_x_psi_p = g2_mul_by_x(&sum, &mut cofactor);
+        let h = g2_step(
+            &[&psi2_2p, &x2_p_x_psi_p, &x_p, &psi_p, &pv],
+            &mut cofactor,
+            |v| {
+                v[0].add(&v[1])
+                    .unwrap()
+                    .add(&v[2].negate().unwrap())
+                    .unwrap()
+                    .add(&v[3].negate().unwrap())
+                    .unwrap()
+                    .add(&v[4].negate().unwrap())
+                    .unwrap()
+            },
+        );
+        assert_eq!(
+            h.value().unwrap(),
+            clear_cofactor_g2::<Parameters>(&p.into_projective()).into_affine()
+        );
+        println!("cofactor clearing: {} constraints", cofactor);
+
+        // Miller loop steps measured on a single pair: `marks` records the
+        // count whenever the loop pulls the next bit of [0, 0, 1].
+        let (p, q) = (
+            G1Projective::rand(&mut rng).into_affine(),
+            G2Projective::rand(&mut rng).into_affine(),
+        );
+        let marks = RefCell::new(vec![]);
+        let total = count(|cs| {
+            let pv = G1AffineVar::new_witness(ns!(cs, "p"), || Ok(p)).unwrap();
+            let qv = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(q)).unwrap();
+            let bits = [false, false, true]
+                .into_iter()
+                .inspect(|_| marks.borrow_mut().push(cs.num_constraints()));
+            miller_loop_bits(&[pv], &[qv], bits).unwrap();
+        });
+        let marks = marks.into_inner();
+        let step = marks[2] - marks[1];
+        let add_step = total - marks[2] - step;
+        let square = fq12_op_cost(|a| {
+            a.square().unwrap();
+        });
+        // 63 steps below the leading bit of x, five of them with additions,
+        // each step squaring f once for both pairs
+        let miller = 63 * (2 * step - square) + 5 * 2 * add_step;
+        println!("two-pair Miller loop: ~{} constraints", miller);
+
+        let cyclotomic_square = fq12_op_cost(|a| {
+            a.cyclotomic_square().unwrap();
+        });
+        let mul = fq12_op_cost(|a| {
+            a.mul(a).unwrap();
+        });
+        let inverse = fq12_op_cost(|a| {
+            a.inverse().unwrap();
+        });
+        let frobenius = fq12_op_cost(|a| {
+            a.frobenius_map(1).unwrap();
+        }) + 2 * fq12_op_cost(|a| {
+            a.frobenius_map(2).unwrap();
+        });
+        // five exponentiations by x at 63 cyclotomic squarings and five
+        // multiplications each, on top of the easy part and the hard part
+        let final_exp = 316 * cyclotomic_square + 34 * mul + inverse + frobenius;
+        println!("final exponentiation: ~{} constraints", final_exp);
+
+        let shared = output + membership + cofactor + miller + final_exp;
+        for (name, cost) in hash_costs {
+            println!("drand round ({}): ~{} constraints", name, cost + shared);
+        }
+    }
+
+    /// Constraint counts of a chained and an unchained round, synthesized
+    /// without the matrices: tens of millions of constraints, which need far
+    /// more memory than the other tests even so.
+    #[test]
+    #[ignore]
+    fn bench_drand() {
+        let mut rng = ark_std::test_rng();
+        let params = get_bls12377_fq_params(2);
+        let (sk, pk) = keygen(&mut rng);
+        let genesis = G2Projective::rand(&mut rng).into_affine();
+        for (name, genesis) in [("chained", Some(genesis)), ("unchained", None)] {
+            let beacon = beacon_chain::<CF>(&params, &sk, genesis, 1).remove(0);
+            let cs = ConstraintSystem::<CF>::new_ref();
+            cs.set_mode(SynthesisMode::Prove {
+                construct_matrices: false,
+            });
+            DrandCircuit::new(pk, beacon, params.clone())
+                .generate_constraints(cs.clone())
+                .unwrap();
+            println!(
+                "drand round ({}): {} constraints",
+                name,
+                cs.num_constraints()
+            );
+        }
+    }
+
+    /// The whole round verification, chained and unchained, against valid,
+    /// tampered and out-of-G2 signatures. Needs far more memory than the
+    /// other tests.
+    #[test]
+    #[ignore]
+    fn drand_circuit() {
+        let mut rng = ark_std::test_rng();
+        let params = get_bls12377_fq_params(2);
+        let (sk, pk) = keygen(&mut rng);
+        let genesis = G2Projective::rand(&mut rng).into_affine();
+        for genesis in [Some(genesis), None] {
+            let beacon = beacon_chain::<CF>(&params, &sk, genesis, 1).remove(0);
+            let tampered = Beacon {
+                signature: sign(&params, &sk, beacon.round + 1, beacon.previous.as_ref()),
+                ..beacon.clone()
+            };
+            let forged = Beacon {
+                signature: (beacon.signature.into_projective()
+                    + small_order_point().into_projective())
+                .into_affine(),
+                ..beacon.clone()
+            };
+            for (beacon, valid) in [(beacon, true), (tampered, false), (forged, false)] {
+                let cs = ConstraintSystem::<CF>::new_ref();
+                let satisfied = DrandCircuit::new(pk, beacon, params.clone())
+                    .generate_constraints(cs.clone())
+                    .is_ok()
+                    && cs.is_satisfied().unwrap();
+                assert_eq!(satisfied, valid);
+                println!("drand round: {} constraints", cs.num_constraints());
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! ElGamal encryption of GT elements, the way the threshold protocol
+//! encrypts its keys, and a circuit proving a ciphertext is well formed:
+//! (C1, C2) = (g^r, m * pk^r) with g = e(g1, g2), for a witnessed r and m.
+use ark_ec::{AffineCurve, PairingEngine};
+use ark_ff::{BigInteger, Field, PrimeField, ToConstraintField};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    bits::boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    pairing::PairingVar,
+    ToConstraintFieldGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters, PoseidonSponge},
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_std::{
+    marker::PhantomData,
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+
+pub struct Ciphertext<I: PairingEngine> {
+    pub c1: I::Fqk,
+    pub c2: I::Fqk,
+}
+
+impl<I: PairingEngine> Clone for Ciphertext<I> {
+    fn clone(&self) -> Self {
+        Self {
+            c1: self.c1,
+            c2: self.c2,
+        }
+    }
+}
+
+/// The GT generator e(g1, g2).
+pub fn generator<I: PairingEngine>() -> I::Fqk {
+    I::pairing(
+        I::G1Affine::prime_subgroup_generator(),
+        I::G2Affine::prime_subgroup_generator(),
+    )
+}
+
+#[allow(dead_code)]
+pub fn keygen<I: PairingEngine, R: Rng + CryptoRng>(rng: &mut R) -> (I::Fr, I::Fqk) {
+    let sk = I::Fr::rand(rng);
+    (sk, generator::<I>().pow(sk.into_repr()))
+}
+
+#[allow(dead_code)]
+pub fn encrypt<I: PairingEngine>(pk: &I::Fqk, m: &I::Fqk, r: &I::Fr) -> Ciphertext<I> {
+    Ciphertext {
+        c1: generator::<I>().pow(r.into_repr()),
+        c2: *m * pk.pow(r.into_repr()),
+    }
+}
+
+#[allow(dead_code)]
+pub fn decrypt<I: PairingEngine>(sk: &I::Fr, ct: &Ciphertext<I>) -> I::Fqk {
+    ct.c2 * ct.c1.pow(sk.into_repr()).inverse().unwrap()
+}
+
+/// Poseidon hash of the coefficients of m, absorbed as in `OpMode::HashGT`.
+pub fn commit<I>(params: &PoseidonParameters<I::Fq>, m: &I::Fqk) -> I::Fq
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    let mut sponge = PoseidonSponge::new(params);
+    sponge.absorb(&m.to_field_elements().unwrap());
+    sponge.squeeze_native_field_elements(1).remove(0)
+}
+
+/// Allocates the little-endian bits of a scalar. The exponents only matter
+/// modulo the group order, so the bits are not range checked.
+pub fn alloc_scalar_bits<I: PairingEngine>(
+    cs: ConstraintSystemRef<I::Fq>,
+    s: Option<I::Fr>,
+) -> Result<Vec<Boolean<I::Fq>>, SynthesisError> {
+    let n = I::Fr::size_in_bits();
+    Vec::<Boolean<I::Fq>>::new_witness(ns!(cs, "scalar bits"), || {
+        let bits = s.ok_or(SynthesisError::AssignmentMissing)?;
+        Ok(bits.into_repr().to_bits_le()[..n].to_vec())
+    })
+}
+
+/// Public key, ciphertext and optional commitment variables.
+type ElGamalInputs<I, IV> = (
+    <IV as PairingVar<I>>::GTVar,
+    <IV as PairingVar<I>>::GTVar,
+    <IV as PairingVar<I>>::GTVar,
+    Option<FpVar<<I as PairingEngine>::Fq>>,
+);
+
+/// Proves that the public ciphertext encrypts a witnessed m under the public
+/// key pk. With Poseidon parameters, m is also bound to a public commitment.
+pub struct GtElGamalCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    pk: I::Fqk,
+    ct: Ciphertext<I>,
+    m: I::Fqk,
+    r: I::Fr,
+    poseidon_params: Option<PoseidonParameters<I::Fq>>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> GtElGamalCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+    I::Fq: Absorb,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    #[allow(dead_code)]
+    pub fn new(
+        pk: I::Fqk,
+        ct: Ciphertext<I>,
+        m: I::Fqk,
+        r: I::Fr,
+        params: Option<PoseidonParameters<I::Fq>>,
+    ) -> Self {
+        Self {
+            pk,
+            ct,
+            m,
+            r,
+            poseidon_params: params,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        pk: I::Fqk,
+        ct: &Ciphertext<I>,
+        commitment: Option<I::Fq>,
+    ) -> Result<ElGamalInputs<I, IV>, SynthesisError> {
+        let pk = IV::GTVar::new_input(ns!(cs, "pk"), || Ok(pk))?;
+        let c1 = IV::GTVar::new_input(ns!(cs, "c1"), || Ok(ct.c1))?;
+        let c2 = IV::GTVar::new_input(ns!(cs, "c2"), || Ok(ct.c2))?;
+        let commitment = commitment
+            .map(|c| FpVar::new_input(ns!(cs, "commitment"), || Ok(c)))
+            .transpose()?;
+        Ok((pk, c1, c2, commitment))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(
+        pk: I::Fqk,
+        ct: &Ciphertext<I>,
+        commitment: Option<I::Fq>,
+    ) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), pk, ct, commitment)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for GtElGamalCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+    IV::GTVar: ToConstraintFieldGadget<I::Fq>,
+    I::Fq: Absorb,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let commitment = self
+            .poseidon_params
+            .as_ref()
+            .map(|params| commit::<I>(params, &self.m));
+        let (pk, c1, c2, commitment) =
+            Self::alloc_inputs(cs.clone(), self.pk, &self.ct, commitment)?;
+        let m = IV::GTVar::new_witness(ns!(cs, "m"), || Ok(self.m))?;
+        let r = alloc_scalar_bits::<I>(cs.clone(), Some(self.r))?;
+
+        let g = IV::GTVar::constant(generator::<I>());
+        c1.enforce_equal(&g.pow_le(&r)?)?;
+        c2.enforce_equal(&(m.clone() * pk.pow_le(&r)?))?;
+
+        if let (Some(params), Some(commitment)) = (&self.poseidon_params, commitment) {
+            let mut sponge = PoseidonSpongeVar::new(cs.clone(), params);
+            sponge.absorb(&m.to_constraint_field()?)?;
+            commitment.enforce_equal(&sponge.squeeze_field_elements(1)?.remove(0))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq};
+    use ark_bw6_761::BW6_761;
+    use ark_ff::One;
+    use ark_groth16::Groth16;
+    use ark_snark::SNARK;
+    use std::time::Instant;
+
+    type Circuit = GtElGamalCircuit<I, IV>;
+    type Fr = <I as PairingEngine>::Fr;
+    type Gt = <I as PairingEngine>::Fqk;
+
+    /// A fresh key pair, message and randomness with the matching ciphertext.
+    fn random_instance<R: Rng + CryptoRng>(rng: &mut R) -> (Fr, Gt, Gt, Fr, Ciphertext<I>) {
+        let (sk, pk) = keygen::<I, _>(rng);
+        let m = generator::<I>().pow(Fr::rand(rng).into_repr());
+        let r = Fr::rand(rng);
+        let ct = encrypt::<I>(&pk, &m, &r);
+        (sk, pk, m, r, ct)
+    }
+
+    #[test]
+    fn native_elgamal() {
+        let mut rng = ark_std::test_rng();
+        let (sk, pk, m, _, ct) = random_instance(&mut rng);
+        assert_eq!(decrypt::<I>(&sk, &ct), m);
+        let (other, _) = keygen::<I, _>(&mut rng);
+        assert_ne!(decrypt::<I>(&other, &ct), m);
+        // multiplicatively homomorphic
+        let m2 = generator::<I>();
+        let ct2 = encrypt::<I>(&pk, &m2, &Fr::rand(&mut rng));
+        let prod = Ciphertext::<I> {
+            c1: ct.c1 * ct2.c1,
+            c2: ct.c2 * ct2.c2,
+        };
+        assert_eq!(decrypt::<I>(&sk, &prod), m * m2);
+    }
+
+    #[test]
+    fn elgamal_circuit() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (_, pk, m, r, ct) = random_instance(&mut rng);
+        for params in [None, Some(params)] {
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            Circuit::new(pk, ct.clone(), m, r, params.clone())
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!(
+                "GT ElGamal ({} commitment): {} constraints",
+                if params.is_some() { "with" } else { "without" },
+                cs.num_constraints()
+            );
+
+            // a different message under the same ciphertext
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            Circuit::new(pk, ct.clone(), m * m, r, params)
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(!cs.is_satisfied().unwrap());
+        }
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let wrong_r = r + Fr::one();
+        Circuit::new(pk, ct, m, wrong_r, None)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn elgamal_groth16() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (_, pk, m, r, ct) = random_instance(&mut rng);
+        let circuit = || Circuit::new(pk, ct.clone(), m, r, Some(params.clone()));
+
+        let start = Instant::now();
+        let (ppk, pvk) = Groth16::<BW6_761>::circuit_specific_setup(circuit(), &mut rng).unwrap();
+        println!("BW6-761 Groth16 setup: {:?}", start.elapsed());
+        let start = Instant::now();
+        let proof = Groth16::<BW6_761>::prove(&ppk, circuit(), &mut rng).unwrap();
+        println!("BW6-761 Groth16 proving: {:?}", start.elapsed());
+        let inputs = Circuit::public_inputs(pk, &ct, Some(commit::<I>(&params, &m))).unwrap();
+        let start = Instant::now();
+        assert!(Groth16::<BW6_761>::verify(&pvk, &inputs, &proof).unwrap());
+        println!("BW6-761 Groth16 verification: {:?}", start.elapsed());
+        let wrong = Circuit::public_inputs(pk, &ct, Some(commit::<I>(&params, &(m * m)))).unwrap();
+        assert!(!Groth16::<BW6_761>::verify(&pvk, &wrong, &proof).unwrap());
+    }
+}
@@ -0,0 +1,147 @@
+//! Emulated BLS12-381 G1 and G2 points in affine coordinates. The addition
+//! formulas are incomplete: they assume the points are non-zero and, for
+//! addition, distinct and not opposite, which holds for the pairing inputs and
+//! scalar multiples of them. Subgroup membership is left to the caller.
+use super::fields::{EmulatedFieldVar, Fq2Var, FqVar};
+use ark_bls12_381::{g1, g2};
+use ark_ec::{short_weierstrass_jacobian::GroupAffine, SWModelParameters};
+use ark_ff::{BitIteratorBE, One, PrimeField, Zero};
+use ark_r1cs_std::alloc::{AllocVar, AllocationMode};
+use ark_relations::{
+    ns,
+    r1cs::{Namespace, SynthesisError},
+};
+use std::{borrow::Borrow, marker::PhantomData};
+
+#[derive(Clone, Debug)]
+pub struct AffineVar<P, F, CF>
+where
+    P: SWModelParameters,
+    F: EmulatedFieldVar<CF, Value = P::BaseField>,
+    CF: PrimeField,
+{
+    pub x: F,
+    pub y: F,
+    _p: PhantomData<(P, CF)>,
+}
+
+pub type G1AffineVar<CF> = AffineVar<g1::Parameters, FqVar<CF>, CF>;
+pub type G2AffineVar<CF> = AffineVar<g2::Parameters, Fq2Var<CF>, CF>;
+
+impl<P, F, CF> AffineVar<P, F, CF>
+where
+    P: SWModelParameters,
+    F: EmulatedFieldVar<CF, Value = P::BaseField>,
+    CF: PrimeField,
+{
+    pub fn new(x: F, y: F) -> Self {
+        Self {
+            x,
+            y,
+            _p: PhantomData,
+        }
+    }
+
+    pub fn constant(p: GroupAffine<P>) -> Self {
+        Self::new(F::constant(p.x), F::constant(p.y))
+    }
+
+    #[allow(dead_code)]
+    pub fn value(&self) -> Result<GroupAffine<P>, SynthesisError> {
+        Ok(GroupAffine::new(self.x.value()?, self.y.value()?, false))
+    }
+
+    /// Enforces y^2 = x^3 + a x + b.
+    pub fn enforce_on_curve(&self) -> Result<(), SynthesisError> {
+        let x2 = self.x.square()?;
+        let mut rhs = x2.mul(&self.x)?.add(&F::constant(P::COEFF_B));
+        if !P::COEFF_A.is_zero() {
+            rhs = rhs.add(&self.x.mul(&F::constant(P::COEFF_A))?);
+        }
+        self.y.square()?.enforce_equal(&rhs)
+    }
+
+    pub fn negate(&self) -> Result<Self, SynthesisError> {
+        Ok(Self::new(self.x.clone(), self.y.negate()?))
+    }
+
+    /// Chord rule with the slope allocated as a witness.
+    pub fn add(&self, other: &Self) -> Result<Self, SynthesisError> {
+        let lambda = F::div_witness(&other.y.sub(&self.y), &other.x.sub(&self.x))?;
+        self.with_slope(&lambda, other)
+    }
+
+    /// [`Self::add`] that also rules out equal abscissas, for which the slope
+    /// of the chord rule is left free: the slope is the numerator times an
+    /// inverse of the denominator, one more multiplication.
+    pub fn add_distinct(&self, other: &Self) -> Result<Self, SynthesisError> {
+        let den = other.x.sub(&self.x);
+        let inv = F::div_witness(&F::constant(One::one()), &den)?;
+        let lambda = other.y.sub(&self.y).mul(&inv)?;
+        self.with_slope(&lambda, other)
+    }
+
+    /// Tangent rule with the slope allocated as a witness.
+    pub fn double(&self) -> Result<Self, SynthesisError> {
+        let x2 = self.x.square()?;
+        let mut num = x2.double()?.add(&x2);
+        if !P::COEFF_A.is_zero() {
+            num = num.add(&F::constant(P::COEFF_A));
+        }
+        let lambda = F::div_witness(&num, &self.y.double()?)?;
+        self.with_slope(&lambda, self)
+    }
+
+    fn with_slope(&self, lambda: &F, other: &Self) -> Result<Self, SynthesisError> {
+        let x3 = lambda.square()?.sub(&self.x).sub(&other.x);
+        let y3 = lambda.mul(&self.x.sub(&x3))?.sub(&self.y);
+        Ok(Self::new(x3, y3))
+    }
+
+    /// Double-and-add by a public constant given as little-endian u64 limbs.
+    /// The additions go through [`Self::add_distinct`], so the result is the
+    /// true multiple or the constraints are unsatisfiable, whatever the order
+    /// of the point: doublings have no free slope on curves with b != 0.
+    pub fn mul_by_constant(&self, scalar: &[u64]) -> Result<Self, SynthesisError> {
+        let mut bits = BitIteratorBE::without_leading_zeros(scalar);
+        assert_eq!(bits.next(), Some(true), "zero scalar");
+        let mut res = Self::new(self.x.clone(), self.y.clone());
+        for bit in bits {
+            res = res.double()?;
+            if bit {
+                res = res.add_distinct(self)?;
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn enforce_equal(&self, other: &Self) -> Result<(), SynthesisError> {
+        self.x.enforce_equal(&other.x)?;
+        self.y.enforce_equal(&other.y)
+    }
+}
+
+impl<P, F, CF> AllocVar<GroupAffine<P>, CF> for AffineVar<P, F, CF>
+where
+    P: SWModelParameters,
+    F: EmulatedFieldVar<CF, Value = P::BaseField> + AllocVar<P::BaseField, CF>,
+    CF: PrimeField,
+{
+    /// Allocates a non-zero point and checks it is on the curve.
+    fn new_variable<T: Borrow<GroupAffine<P>>>(
+        cs: impl Into<Namespace<CF>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        let ns = cs.into();
+        let cs = ns.cs();
+        let p = f().map(|p| *p.borrow());
+        let x = F::new_variable(ns!(cs, "x"), || p.map(|p| p.x), mode)?;
+        let y = F::new_variable(ns!(cs, "y"), || p.map(|p| p.y), mode)?;
+        let p = Self::new(x, y);
+        if mode != AllocationMode::Constant {
+            p.enforce_on_curve()?;
+        }
+        Ok(p)
+    }
+}
@@ -0,0 +1,707 @@
+//! BLS12-381 extension tower Fq2 / Fq6 / Fq12 over emulated Fq limbs. The
+//! arkworks extension gadgets require the constraint field to be the base
+//! prime field of the extension, so the tower is rebuilt here on top of
+//! `NonNativeFieldVar<ark_bls12_381::Fq, CF>` following the native formulas
+//! of ark-ff.
+use ark_bls12_381::{Fq, Fq12, Fq12Parameters, Fq2, Fq6, Fq6Parameters};
+use ark_ff::{
+    fields::{Fp12Parameters, Fp6Parameters},
+    BigInteger, Field, One, PrimeField,
+};
+use ark_nonnative_field::{
+    params::{get_params, OptimizationType},
+    reduce::Reducer,
+    AllocatedNonNativeFieldVar, NonNativeFieldParams, NonNativeFieldVar,
+};
+use ark_r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    bits::boolean::Boolean,
+    eq::EqGadget,
+    fields::FieldVar,
+    select::CondSelectGadget,
+    R1CSVar,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSystemRef, Namespace, OptimizationGoal, SynthesisError},
+};
+use std::{borrow::Borrow, marker::PhantomData};
+
+pub type FqVar<CF> = NonNativeFieldVar<Fq, CF>;
+
+/// `a - b` over emulated Fq. ark-nonnative sizes its reductions from the
+/// addition counter, assuming every limb is below (counter + 1) times its
+/// bound in the normal form. Its limb subtraction pads every limb by 2^surfeit
+/// times that bound, i.e. by about 2^surfeit times p, where 2^surfeit exceeds
+/// twice the counter of `b` plus one, while its addition counter only grows by
+/// twice the counter of `b` plus two. Multiplications then size
+/// the quotient by p from that counter, so long chains of subtractions (as in
+/// the Miller loop and the cyclotomic squarings) fail the limb equality
+/// checks. The counter is corrected here before the usual post-addition
+/// reduction. Like the arithmetic operators of ark-r1cs-std, this panics on
+/// synthesis errors.
+pub fn sub_fq<CF: PrimeField>(a: &FqVar<CF>, b: &FqVar<CF>) -> FqVar<CF> {
+    sub_allocated(a, b).unwrap()
+}
+
+fn sub_allocated<CF: PrimeField>(
+    a: &FqVar<CF>,
+    b: &FqVar<CF>,
+) -> Result<FqVar<CF>, SynthesisError> {
+    let cs = a.cs().or(b.cs());
+    let (a, mut b) = match (a, b) {
+        (NonNativeFieldVar::Constant(x), NonNativeFieldVar::Constant(y)) => {
+            return Ok(NonNativeFieldVar::Constant(*x - y))
+        }
+        (NonNativeFieldVar::Var(x), NonNativeFieldVar::Var(y)) => (x.clone(), y.clone()),
+        (NonNativeFieldVar::Var(x), NonNativeFieldVar::Constant(y)) => (
+            x.clone(),
+            AllocatedNonNativeFieldVar::constant(cs.clone(), *y)?,
+        ),
+        (NonNativeFieldVar::Constant(x), NonNativeFieldVar::Var(y)) => (
+            AllocatedNonNativeFieldVar::constant(cs.clone(), *x)?,
+            y.clone(),
+        ),
+    };
+    let params = limb_params(&cs);
+    let top_bits = Fq::size_in_bits() - params.bits_per_limb * (params.num_limbs - 1);
+    let mut surfeit = overhead(b.num_of_additions_over_normal_form + CF::one()) + 1;
+    if surfeit + params.bits_per_limb.max(top_bits) > CF::size_in_bits() - 1 {
+        Reducer::reduce(&mut b)?;
+        surfeit = overhead(b.num_of_additions_over_normal_form + CF::one()) + 1;
+    }
+    let mut res = a.sub_without_reduce(&b)?;
+    // a + the padding, counted in multiples of p > 2^(|p| - 1), + the normal
+    // form limbs that bring the padding to a multiple of p
+    let excess = params.bits_per_limb * params.num_limbs + 1 - Fq::size_in_bits();
+    res.num_of_additions_over_normal_form = a.num_of_additions_over_normal_form
+        + CF::from(2u64).pow([(surfeit + excess) as u64])
+        + CF::one();
+    Reducer::post_add_reduce(&mut res)?;
+    Ok(NonNativeFieldVar::Var(res))
+}
+
+/// Packs fewer than |p| little-endian bits into an emulated Fq element, which
+/// is then already in the normal form: only linear combinations are needed.
+pub fn fq_from_bits_le<CF: PrimeField>(bits: &[Boolean<CF>]) -> Result<FqVar<CF>, SynthesisError> {
+    assert!(bits.len() < Fq::size_in_bits());
+    let cs = bits.cs();
+    if cs.is_none() {
+        let v = bits
+            .iter()
+            .map(|b| b.value())
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(FieldVar::constant(
+            Fq::from_repr(BigInteger::from_bits_le(&v)).unwrap(),
+        ));
+    }
+    let params = limb_params(&cs);
+    let mut bits = bits.to_vec();
+    bits.resize(params.num_limbs * params.bits_per_limb, Boolean::FALSE);
+    // limbs are stored most significant first
+    let limbs = bits
+        .chunks(params.bits_per_limb)
+        .rev()
+        .map(Boolean::le_bits_to_fp_var)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(NonNativeFieldVar::Var(AllocatedNonNativeFieldVar {
+        cs,
+        limbs,
+        num_of_additions_over_normal_form: CF::zero(),
+        is_in_the_normal_form: true,
+        target_phantom: PhantomData,
+    }))
+}
+
+/// Limb layout ark-nonnative picks for emulated Fq in `cs`.
+fn limb_params<CF: PrimeField>(cs: &ConstraintSystemRef<CF>) -> NonNativeFieldParams {
+    let optimization = match cs.optimization_goal() {
+        OptimizationGoal::Weight => OptimizationType::Weight,
+        _ => OptimizationType::Constraints,
+    };
+    get_params(Fq::size_in_bits(), CF::size_in_bits(), optimization)
+}
+
+/// Bit length of a small addition counter, mirroring ark-nonnative.
+fn overhead<CF: PrimeField>(n: CF) -> usize {
+    let bits = n.into_repr().to_bits_be();
+    bits.len() - bits.iter().take_while(|b| !**b).count()
+}
+
+pub fn negate_fq<CF: PrimeField>(a: &FqVar<CF>) -> FqVar<CF> {
+    sub_fq(&FieldVar::zero(), a)
+}
+
+/// Operations shared by the emulated Fq and Fq2 variables, so the curve
+/// gadgets can be written once for G1 and G2.
+pub trait EmulatedFieldVar<CF: PrimeField>: Sized + Clone {
+    type Value: Field;
+
+    fn constant(v: Self::Value) -> Self;
+    fn value(&self) -> Result<Self::Value, SynthesisError>;
+    fn add(&self, other: &Self) -> Self;
+    fn sub(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Result<Self, SynthesisError>;
+    fn square(&self) -> Result<Self, SynthesisError>;
+    fn double(&self) -> Result<Self, SynthesisError>;
+    fn negate(&self) -> Result<Self, SynthesisError>;
+    fn enforce_equal(&self, other: &Self) -> Result<(), SynthesisError>;
+    /// Allocates `num / den` as a witness and enforces `res * den == num`.
+    fn div_witness(num: &Self, den: &Self) -> Result<Self, SynthesisError>;
+}
+
+impl<CF: PrimeField> EmulatedFieldVar<CF> for FqVar<CF> {
+    type Value = Fq;
+
+    fn constant(v: Fq) -> Self {
+        FieldVar::constant(v)
+    }
+
+    fn value(&self) -> Result<Fq, SynthesisError> {
+        R1CSVar::value(self)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        sub_fq(self, other)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, SynthesisError> {
+        Ok(self * other)
+    }
+
+    fn square(&self) -> Result<Self, SynthesisError> {
+        FieldVar::square(self)
+    }
+
+    fn double(&self) -> Result<Self, SynthesisError> {
+        FieldVar::double(self)
+    }
+
+    fn negate(&self) -> Result<Self, SynthesisError> {
+        Ok(negate_fq(self))
+    }
+
+    fn enforce_equal(&self, other: &Self) -> Result<(), SynthesisError> {
+        EqGadget::enforce_equal(self, other)
+    }
+
+    fn div_witness(num: &Self, den: &Self) -> Result<Self, SynthesisError> {
+        let cs = num.cs().or(den.cs());
+        let res = Self::new_witness(ns!(cs, "quotient"), || {
+            let d = R1CSVar::value(den)?;
+            let inv = d.inverse().ok_or(SynthesisError::DivisionByZero)?;
+            Ok(R1CSVar::value(num)? * inv)
+        })?;
+        EqGadget::enforce_equal(&(&res * den), num)?;
+        Ok(res)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Fq2Var<CF: PrimeField> {
+    pub c0: FqVar<CF>,
+    pub c1: FqVar<CF>,
+}
+
+impl<CF: PrimeField> Fq2Var<CF> {
+    pub fn new(c0: FqVar<CF>, c1: FqVar<CF>) -> Self {
+        Self { c0, c1 }
+    }
+
+    #[allow(dead_code)]
+    pub fn zero() -> Self {
+        EmulatedFieldVar::constant(Fq2::new(Fq::from(0u64), Fq::from(0u64)))
+    }
+
+    pub fn one() -> Self {
+        EmulatedFieldVar::constant(Fq2::new(Fq::from(1u64), Fq::from(0u64)))
+    }
+
+    /// Multiplication by the Fq6 non-residue u + 1.
+    pub fn mul_by_nonresidue(&self) -> Self {
+        Self::new(sub_fq(&self.c0, &self.c1), &self.c0 + &self.c1)
+    }
+
+    pub fn mul_by_fp(&self, other: &FqVar<CF>) -> Self {
+        Self::new(&self.c0 * other, &self.c1 * other)
+    }
+
+    pub fn mul_by_constant(&self, other: Fq2) -> Self {
+        let v0 = &self.c0 * other.c0;
+        let v1 = &self.c1 * other.c1;
+        let c1 = (&self.c0 + &self.c1) * (other.c0 + other.c1);
+        let c1 = sub_fq(&sub_fq(&c1, &v0), &v1);
+        Self::new(sub_fq(&v0, &v1), c1)
+    }
+
+    pub fn conjugate(&self) -> Result<Self, SynthesisError> {
+        Ok(Self::new(self.c0.clone(), negate_fq(&self.c1)))
+    }
+
+    pub fn conditionally_select(
+        cond: &Boolean<CF>,
+        true_value: &Self,
+        false_value: &Self,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self::new(
+            FqVar::conditionally_select(cond, &true_value.c0, &false_value.c0)?,
+            FqVar::conditionally_select(cond, &true_value.c1, &false_value.c1)?,
+        ))
+    }
+
+    /// The Frobenius coefficient of Fq2 is -1, so odd powers conjugate.
+    pub fn frobenius_map(&self, power: usize) -> Result<Self, SynthesisError> {
+        match power % 2 {
+            0 => Ok(self.clone()),
+            _ => self.conjugate(),
+        }
+    }
+}
+
+impl<CF: PrimeField> EmulatedFieldVar<CF> for Fq2Var<CF> {
+    type Value = Fq2;
+
+    fn constant(v: Fq2) -> Self {
+        Self::new(FieldVar::constant(v.c0), FieldVar::constant(v.c1))
+    }
+
+    fn value(&self) -> Result<Fq2, SynthesisError> {
+        Ok(Fq2::new(
+            R1CSVar::value(&self.c0)?,
+            R1CSVar::value(&self.c1)?,
+        ))
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self::new(&self.c0 + &other.c0, &self.c1 + &other.c1)
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        Self::new(sub_fq(&self.c0, &other.c0), sub_fq(&self.c1, &other.c1))
+    }
+
+    /// Karatsuba with u^2 = -1: three multiplications.
+    fn mul(&self, other: &Self) -> Result<Self, SynthesisError> {
+        let v0 = &self.c0 * &other.c0;
+        let v1 = &self.c1 * &other.c1;
+        let c1 = (&self.c0 + &self.c1) * (&other.c0 + &other.c1);
+        let c1 = sub_fq(&sub_fq(&c1, &v0), &v1);
+        Ok(Self::new(sub_fq(&v0, &v1), c1))
+    }
+
+    /// Complex squaring: two multiplications.
+    fn square(&self) -> Result<Self, SynthesisError> {
+        let c0 = (&self.c0 + &self.c1) * sub_fq(&self.c0, &self.c1);
+        let c1 = FieldVar::double(&(&self.c0 * &self.c1))?;
+        Ok(Self::new(c0, c1))
+    }
+
+    fn double(&self) -> Result<Self, SynthesisError> {
+        Ok(Self::new(
+            FieldVar::double(&self.c0)?,
+            FieldVar::double(&self.c1)?,
+        ))
+    }
+
+    fn negate(&self) -> Result<Self, SynthesisError> {
+        Ok(Self::new(negate_fq(&self.c0), negate_fq(&self.c1)))
+    }
+
+    fn enforce_equal(&self, other: &Self) -> Result<(), SynthesisError> {
+        EqGadget::enforce_equal(&self.c0, &other.c0)?;
+        EqGadget::enforce_equal(&self.c1, &other.c1)
+    }
+
+    fn div_witness(num: &Self, den: &Self) -> Result<Self, SynthesisError> {
+        let cs = num.c0.cs().or(num.c1.cs()).or(den.c0.cs()).or(den.c1.cs());
+        let res = Self::new_witness(ns!(cs, "quotient"), || {
+            let d = den.value()?;
+            let inv = d.inverse().ok_or(SynthesisError::DivisionByZero)?;
+            Ok(num.value()? * inv)
+        })?;
+        res.mul(den)?.enforce_equal(num)?;
+        Ok(res)
+    }
+}
+
+impl<CF: PrimeField> AllocVar<Fq2, CF> for Fq2Var<CF> {
+    fn new_variable<T: Borrow<Fq2>>(
+        cs: impl Into<Namespace<CF>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        let ns = cs.into();
+        let cs = ns.cs();
+        let v = f().map(|v| *v.borrow());
+        let c0 = FqVar::new_variable(ns!(cs, "c0"), || v.map(|v| v.c0), mode)?;
+        let c1 = FqVar::new_variable(ns!(cs, "c1"), || v.map(|v| v.c1), mode)?;
+        Ok(Self::new(c0, c1))
+    }
+}
+
+/// Fq6 = Fq2[v] / (v^3 - (u + 1)).
+#[derive(Clone, Debug)]
+pub struct Fq6Var<CF: PrimeField> {
+    pub c0: Fq2Var<CF>,
+    pub c1: Fq2Var<CF>,
+    pub c2: Fq2Var<CF>,
+}
+
+impl<CF: PrimeField> Fq6Var<CF> {
+    pub fn new(c0: Fq2Var<CF>, c1: Fq2Var<CF>, c2: Fq2Var<CF>) -> Self {
+        Self { c0, c1, c2 }
+    }
+
+    pub fn constant(v: Fq6) -> Self {
+        Self::new(
+            Fq2Var::constant(v.c0),
+            Fq2Var::constant(v.c1),
+            Fq2Var::constant(v.c2),
+        )
+    }
+
+    pub fn value(&self) -> Result<Fq6, SynthesisError> {
+        Ok(Fq6::new(
+            self.c0.value()?,
+            self.c1.value()?,
+            self.c2.value()?,
+        ))
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        Self::new(
+            self.c0.add(&other.c0),
+            self.c1.add(&other.c1),
+            self.c2.add(&other.c2),
+        )
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        Self::new(
+            self.c0.sub(&other.c0),
+            self.c1.sub(&other.c1),
+            self.c2.sub(&other.c2),
+        )
+    }
+
+    pub fn negate(&self) -> Result<Self, SynthesisError> {
+        Ok(Self::new(
+            self.c0.negate()?,
+            self.c1.negate()?,
+            self.c2.negate()?,
+        ))
+    }
+
+    /// Multiplication by the Fq12 non-residue v.
+    pub fn mul_by_nonresidue(&self) -> Self {
+        Self::new(
+            self.c2.mul_by_nonresidue(),
+            self.c0.clone(),
+            self.c1.clone(),
+        )
+    }
+
+    /// Karatsuba: six Fq2 multiplications.
+    pub fn mul(&self, other: &Self) -> Result<Self, SynthesisError> {
+        let v0 = self.c0.mul(&other.c0)?;
+        let v1 = self.c1.mul(&other.c1)?;
+        let v2 = self.c2.mul(&other.c2)?;
+        let c0 = self
+            .c1
+            .add(&self.c2)
+            .mul(&other.c1.add(&other.c2))?
+            .sub(&v1)
+            .sub(&v2)
+            .mul_by_nonresidue()
+            .add(&v0);
+        let c1 = self
+            .c0
+            .add(&self.c1)
+            .mul(&other.c0.add(&other.c1))?
+            .sub(&v0)
+            .sub(&v1)
+            .add(&v2.mul_by_nonresidue());
+        let c2 = self
+            .c0
+            .add(&self.c2)
+            .mul(&other.c0.add(&other.c2))?
+            .sub(&v0)
+            .add(&v1)
+            .sub(&v2);
+        Ok(Self::new(c0, c1, c2))
+    }
+
+    /// Chung-Hasan SQR2: two Fq2 multiplications and three squarings.
+    #[allow(dead_code)]
+    pub fn square(&self) -> Result<Self, SynthesisError> {
+        let s0 = self.c0.square()?;
+        let s1 = self.c0.mul(&self.c1)?.double()?;
+        let s2 = self.c0.sub(&self.c1).add(&self.c2).square()?;
+        let s3 = self.c1.mul(&self.c2)?.double()?;
+        let s4 = self.c2.square()?;
+        let c0 = s0.add(&s3.mul_by_nonresidue());
+        let c1 = s1.add(&s4.mul_by_nonresidue());
+        let c2 = s1.add(&s2).add(&s3).sub(&s0).sub(&s4);
+        Ok(Self::new(c0, c1, c2))
+    }
+
+    #[allow(dead_code)]
+    pub fn mul_by_fq2(&self, other: &Fq2Var<CF>) -> Result<Self, SynthesisError> {
+        Ok(Self::new(
+            self.c0.mul(other)?,
+            self.c1.mul(other)?,
+            self.c2.mul(other)?,
+        ))
+    }
+
+    /// Multiplication by c0 + c1 v.
+    pub fn mul_by_01(&self, c0: &Fq2Var<CF>, c1: &Fq2Var<CF>) -> Result<Self, SynthesisError> {
+        let a_a = self.c0.mul(c0)?;
+        let b_b = self.c1.mul(c1)?;
+        let t1 = c1
+            .mul(&self.c1.add(&self.c2))?
+            .sub(&b_b)
+            .mul_by_nonresidue()
+            .add(&a_a);
+        let t3 = c0.mul(&self.c0.add(&self.c2))?.sub(&a_a).add(&b_b);
+        let t2 = c0.add(c1).mul(&self.c0.add(&self.c1))?.sub(&a_a).sub(&b_b);
+        Ok(Self::new(t1, t2, t3))
+    }
+
+    /// Multiplication by c1 v.
+    pub fn mul_by_1(&self, c1: &Fq2Var<CF>) -> Result<Self, SynthesisError> {
+        let b_b = self.c1.mul(c1)?;
+        let t1 = c1
+            .mul(&self.c1.add(&self.c2))?
+            .sub(&b_b)
+            .mul_by_nonresidue();
+        let t2 = c1.mul(&self.c0.add(&self.c1))?.sub(&b_b);
+        Ok(Self::new(t1, t2, b_b))
+    }
+
+    pub fn frobenius_map(&self, power: usize) -> Result<Self, SynthesisError> {
+        let c0 = self.c0.frobenius_map(power)?;
+        let mut c1 = self.c1.frobenius_map(power)?;
+        let mut c2 = self.c2.frobenius_map(power)?;
+        let i = power % 6;
+        if i != 0 {
+            c1 = c1.mul_by_constant(Fq6Parameters::FROBENIUS_COEFF_FP6_C1[i]);
+            c2 = c2.mul_by_constant(Fq6Parameters::FROBENIUS_COEFF_FP6_C2[i]);
+        }
+        Ok(Self::new(c0, c1, c2))
+    }
+
+    pub fn enforce_equal(&self, other: &Self) -> Result<(), SynthesisError> {
+        self.c0.enforce_equal(&other.c0)?;
+        self.c1.enforce_equal(&other.c1)?;
+        self.c2.enforce_equal(&other.c2)
+    }
+}
+
+impl<CF: PrimeField> AllocVar<Fq6, CF> for Fq6Var<CF> {
+    fn new_variable<T: Borrow<Fq6>>(
+        cs: impl Into<Namespace<CF>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        let ns = cs.into();
+        let cs = ns.cs();
+        let v = f().map(|v| *v.borrow());
+        let c0 = Fq2Var::new_variable(ns!(cs, "c0"), || v.map(|v| v.c0), mode)?;
+        let c1 = Fq2Var::new_variable(ns!(cs, "c1"), || v.map(|v| v.c1), mode)?;
+        let c2 = Fq2Var::new_variable(ns!(cs, "c2"), || v.map(|v| v.c2), mode)?;
+        Ok(Self::new(c0, c1, c2))
+    }
+}
+
+/// Fq12 = Fq6[w] / (w^2 - v), the target group of the emulated pairing.
+#[derive(Clone, Debug)]
+pub struct Fq12Var<CF: PrimeField> {
+    pub c0: Fq6Var<CF>,
+    pub c1: Fq6Var<CF>,
+}
+
+impl<CF: PrimeField> Fq12Var<CF> {
+    pub fn new(c0: Fq6Var<CF>, c1: Fq6Var<CF>) -> Self {
+        Self { c0, c1 }
+    }
+
+    pub fn constant(v: Fq12) -> Self {
+        Self::new(Fq6Var::constant(v.c0), Fq6Var::constant(v.c1))
+    }
+
+    pub fn one() -> Self {
+        Self::constant(Fq12::one())
+    }
+
+    pub fn value(&self) -> Result<Fq12, SynthesisError> {
+        Ok(Fq12::new(self.c0.value()?, self.c1.value()?))
+    }
+
+    /// Karatsuba: three Fq6 multiplications.
+    pub fn mul(&self, other: &Self) -> Result<Self, SynthesisError> {
+        let v0 = self.c0.mul(&other.c0)?;
+        let v1 = self.c1.mul(&other.c1)?;
+        let c1 = self
+            .c0
+            .add(&self.c1)
+            .mul(&other.c0.add(&other.c1))?
+            .sub(&v0)
+            .sub(&v1);
+        Ok(Self::new(v0.add(&v1.mul_by_nonresidue()), c1))
+    }
+
+    /// Complex squaring: two Fq6 multiplications.
+    pub fn square(&self) -> Result<Self, SynthesisError> {
+        let c0c1 = self.c0.mul(&self.c1)?;
+        let c0 = self
+            .c0
+            .add(&self.c1)
+            .mul(&self.c0.add(&self.c1.mul_by_nonresidue()))?
+            .sub(&c0c1)
+            .sub(&c0c1.mul_by_nonresidue());
+        Ok(Self::new(c0, c0c1.add(&c0c1)))
+    }
+
+    /// Inverse of unitary elements, i.e. of anything after the easy part of
+    /// the final exponentiation.
+    pub fn conjugate(&self) -> Result<Self, SynthesisError> {
+        Ok(Self::new(self.c0.clone(), self.c1.negate()?))
+    }
+
+    /// Multiplication by the sparse line c0 + c1 v + c4 v w of an M-type
+    /// twist.
+    pub fn mul_by_014(
+        &self,
+        c0: &Fq2Var<CF>,
+        c1: &Fq2Var<CF>,
+        c4: &Fq2Var<CF>,
+    ) -> Result<Self, SynthesisError> {
+        let aa = self.c0.mul_by_01(c0, c1)?;
+        let bb = self.c1.mul_by_1(c4)?;
+        let o = c1.add(c4);
+        let new_c1 = self.c1.add(&self.c0).mul_by_01(c0, &o)?.sub(&aa).sub(&bb);
+        let new_c0 = bb.mul_by_nonresidue().add(&aa);
+        Ok(Self::new(new_c0, new_c1))
+    }
+
+    pub fn frobenius_map(&self, power: usize) -> Result<Self, SynthesisError> {
+        let c0 = self.c0.frobenius_map(power)?;
+        let mut c1 = self.c1.frobenius_map(power)?;
+        let i = power % 12;
+        if i != 0 {
+            let coeff = Fq12Parameters::FROBENIUS_COEFF_FP12_C1[i];
+            c1 = Fq6Var::new(
+                c1.c0.mul_by_constant(coeff),
+                c1.c1.mul_by_constant(coeff),
+                c1.c2.mul_by_constant(coeff),
+            );
+        }
+        Ok(Self::new(c0, c1))
+    }
+
+    /// Allocates the inverse as a witness and checks it with one
+    /// multiplication.
+    pub fn inverse(&self) -> Result<Self, SynthesisError> {
+        let cs = self.cs();
+        let inv = Self::new_witness(ns!(cs, "inverse"), || {
+            self.value()?
+                .inverse()
+                .ok_or(SynthesisError::DivisionByZero)
+        })?;
+        self.mul(&inv)?.enforce_equal(&Self::one())?;
+        Ok(inv)
+    }
+
+    /// Granger-Scott squaring, only valid in the cyclotomic subgroup.
+    pub fn cyclotomic_square(&self) -> Result<Self, SynthesisError> {
+        let r0 = &self.c0.c0;
+        let r4 = &self.c0.c1;
+        let r3 = &self.c0.c2;
+        let r2 = &self.c1.c0;
+        let r1 = &self.c1.c1;
+        let r5 = &self.c1.c2;
+
+        // (a + b y)^2 = t0 + t1 y for the three pairs (r0, r1), (r2, r3)
+        // and (r4, r5)
+        let fp4_square = |a: &Fq2Var<CF>, b: &Fq2Var<CF>| -> Result<_, SynthesisError> {
+            let tmp = a.mul(b)?;
+            let t0 = a
+                .add(b)
+                .mul(&b.mul_by_nonresidue().add(a))?
+                .sub(&tmp)
+                .sub(&tmp.mul_by_nonresidue());
+            Ok((t0, tmp.double()?))
+        };
+        let (t0, t1) = fp4_square(r0, r1)?;
+        let (t2, t3) = fp4_square(r2, r3)?;
+        let (t4, t5) = fp4_square(r4, r5)?;
+
+        // z = 3 t - 2 z or z = 3 t + 2 z depending on the coefficient
+        let minus = |t: &Fq2Var<CF>, z: &Fq2Var<CF>| -> Result<_, SynthesisError> {
+            Ok(t.sub(z).double()?.add(t))
+        };
+        let plus = |t: &Fq2Var<CF>, z: &Fq2Var<CF>| -> Result<_, SynthesisError> {
+            Ok(t.add(z).double()?.add(t))
+        };
+        let z0 = minus(&t0, r0)?;
+        let z1 = plus(&t1, r1)?;
+        let z2 = plus(&t5.mul_by_nonresidue(), r2)?;
+        let z3 = minus(&t4, r3)?;
+        let z4 = minus(&t2, r4)?;
+        let z5 = plus(&t3, r5)?;
+        Ok(Self::new(Fq6Var::new(z0, z4, z3), Fq6Var::new(z2, z1, z5)))
+    }
+
+    /// Square and multiply with a constant exponent given in little-endian
+    /// limbs, for elements of the cyclotomic subgroup.
+    pub fn cyclotomic_exp(&self, exp: &[u64]) -> Result<Self, SynthesisError> {
+        let mut res: Option<Self> = None;
+        for bit in ark_ff::BitIteratorBE::new(exp) {
+            if let Some(r) = res.as_mut() {
+                *r = r.cyclotomic_square()?;
+            }
+            if bit {
+                res = Some(match res {
+                    Some(r) => r.mul(self)?,
+                    None => self.clone(),
+                });
+            }
+        }
+        Ok(res.unwrap_or_else(Self::one))
+    }
+
+    pub fn enforce_equal(&self, other: &Self) -> Result<(), SynthesisError> {
+        self.c0.enforce_equal(&other.c0)?;
+        self.c1.enforce_equal(&other.c1)
+    }
+
+    fn cs(&self) -> ark_relations::r1cs::ConstraintSystemRef<CF> {
+        [&self.c0, &self.c1]
+            .iter()
+            .flat_map(|c| [&c.c0, &c.c1, &c.c2])
+            .flat_map(|c| [&c.c0, &c.c1])
+            .fold(ark_relations::r1cs::ConstraintSystemRef::None, |cs, c| {
+                cs.or(c.cs())
+            })
+    }
+}
+
+impl<CF: PrimeField> AllocVar<Fq12, CF> for Fq12Var<CF> {
+    fn new_variable<T: Borrow<Fq12>>(
+        cs: impl Into<Namespace<CF>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        let ns = cs.into();
+        let cs = ns.cs();
+        let v = f().map(|v| *v.borrow());
+        let c0 = Fq6Var::new_variable(ns!(cs, "c0"), || v.map(|v| v.c0), mode)?;
+        let c1 = Fq6Var::new_variable(ns!(cs, "c1"), || v.map(|v| v.c1), mode)?;
+        Ok(Self::new(c0, c1))
+    }
+}
@@ -0,0 +1,405 @@
+//! Hash-to-curve onto BLS12-381 G2 inside another constraint field.
+//!
+//! Messages are vectors of constraint field elements hashed with the Poseidon
+//! sponge of that field; the squeezed elements are packed into Fq2 elements
+//! and mapped onto the curve with the Shallue-van de Woestijne map of the
+//! hash-to-curve RFC, which unlike simplified SWU needs no isogeny for a = 0.
+//! The cofactor is cleared with the psi endomorphism, natively with the
+//! helpers of [`crate::hash_to_curve`].
+use super::{
+    curves::G2AffineVar,
+    fields::{fq_from_bits_le, EmulatedFieldVar, Fq2Var},
+};
+use crate::hash_to_curve::{
+    clear_cofactor_g2, hash_to_field, hash_to_field_var, sgn0_quadratic_var, PsiParameters, Sgn0,
+    Sgn0Gadget,
+};
+use ark_bls12_381::{g2, Fq, Fq2, Fq6Parameters, G2Affine, Parameters};
+use ark_ec::{bls12::Bls12Parameters, AffineCurve, ProjectiveCurve, SWModelParameters};
+use ark_ff::{
+    field_new, fields::Fp6Parameters, BigInteger, Field, One, PrimeField, SquareRootField, Zero,
+};
+use ark_r1cs_std::{
+    alloc::AllocVar, bits::boolean::Boolean, eq::EqGadget, fields::fp::FpVar, R1CSVar, ToBitsGadget,
+};
+use ark_relations::{ns, r1cs::SynthesisError};
+use ark_sponge::{poseidon::PoseidonParameters, Absorb};
+
+pub const G2_DOMAIN: &[u8] = b"BLS12381G2_POSEIDON_SVDW_RO_";
+
+impl<CF: PrimeField> Sgn0Gadget<CF> for Fq2Var<CF> {
+    fn sgn0(&self) -> Result<Boolean<CF>, SynthesisError> {
+        sgn0_quadratic_var(&self.c0, &self.c1)
+    }
+}
+
+/// Constants of the Shallue-van de Woestijne map for y^2 = g(x) = x^3 + B,
+/// named as in the RFC: c1 = g(Z), c2 = -Z / 2, c3 = sqrt(-3 Z^2 g(Z)) with
+/// sgn0(c3) = 0 and c4 = -4 g(Z) / (3 Z^2).
+struct SvdwConstants {
+    z: Fq2,
+    c1: Fq2,
+    c2: Fq2,
+    c3: Fq2,
+    c4: Fq2,
+}
+
+fn g(x: Fq2) -> Fq2 {
+    x.square() * x + g2::Parameters::COEFF_B
+}
+
+fn is_square(x: Fq2) -> bool {
+    x.sqrt().is_some()
+}
+
+lazy_static! {
+    static ref SVDW: SvdwConstants = svdw_constants();
+}
+
+/// Z is the first of 1, -1, 2, -2, ... meeting the criteria of the RFC.
+fn svdw_constants() -> SvdwConstants {
+    let (two, three, four) = (Fq2::from(2u64), Fq2::from(3u64), Fq2::from(4u64));
+    let z = (1u64..)
+        .flat_map(|i| [Fq2::from(i), -Fq2::from(i)])
+        .find(|z| {
+            let gz = g(*z);
+            !gz.is_zero()
+                && is_square(-three * z.square() / (four * gz))
+                && (is_square(gz) || is_square(g(-*z / two)))
+        })
+        .unwrap();
+    let gz = g(z);
+    let three_z2 = three * z.square();
+    let c3 = (-gz * three_z2).sqrt().unwrap();
+    SvdwConstants {
+        z,
+        c1: gz,
+        c2: -z / two,
+        c3: if c3.sgn0() { -c3 } else { c3 },
+        c4: -four * gz / three_z2,
+    }
+}
+
+/// Shallue-van de Woestijne map onto the twist, not in the subgroup yet.
+pub fn map_to_curve(u: Fq2) -> G2Affine {
+    let c = &*SVDW;
+    let tv1 = u.square() * c.c1;
+    let tv2 = Fq2::one() + tv1;
+    let tv1 = Fq2::one() - tv1;
+    let tv3 = (tv1 * tv2).inverse().unwrap_or_else(Fq2::zero);
+    let tv4 = u * tv1 * tv3 * c.c3;
+    let x1 = c.c2 - tv4;
+    let x2 = c.c2 + tv4;
+    let x3 = (tv2.square() * tv3).square() * c.c4 + c.z;
+    let x = if is_square(g(x1)) {
+        x1
+    } else if is_square(g(x2)) {
+        x2
+    } else {
+        x3
+    };
+    let y = g(x).sqrt().unwrap();
+    let y = if y.sgn0() != u.sgn0() { -y } else { y };
+    G2Affine::new(x, y, false)
+}
+
+fn g_var<CF: PrimeField>(x: &Fq2Var<CF>) -> Result<Fq2Var<CF>, SynthesisError> {
+    Ok(x.square()?
+        .mul(x)?
+        .add(&Fq2Var::constant(g2::Parameters::COEFF_B)))
+}
+
+/// Witnesses whether `x` is a square, with a square root of either `x` or
+/// `XI * x` for the non-square `XI` of the tower, exactly one of which exists.
+fn is_square_var<CF: PrimeField>(x: &Fq2Var<CF>) -> Result<Boolean<CF>, SynthesisError> {
+    let cs = x.c0.cs().or(x.c1.cs());
+    let xi = Fq6Parameters::NONRESIDUE;
+    let b = Boolean::new_witness(ns!(cs, "is_square"), || Ok(is_square(x.value()?)))?;
+    let root = Fq2Var::new_witness(ns!(cs, "root"), || {
+        let x = x.value()?;
+        Ok(x.sqrt().or_else(|| (xi * x).sqrt()).unwrap())
+    })?;
+    let target = Fq2Var::conditionally_select(&b, x, &x.mul_by_constant(xi))?;
+    root.square()?.enforce_equal(&target)?;
+    Ok(b)
+}
+
+/// R1CS version of [`map_to_curve`]. Like the native map with `inv0`, it
+/// never fails on the exceptional inputs u^2 c1 = ±1, but then the inverse
+/// witness cannot satisfy its check; those u occur with negligible
+/// probability.
+pub fn map_to_curve_var<CF: PrimeField>(u: &Fq2Var<CF>) -> Result<G2AffineVar<CF>, SynthesisError> {
+    let c = &*SVDW;
+    let cs = u.c0.cs().or(u.c1.cs());
+    let one = Fq2Var::one();
+    let tv1 = u.square()?.mul_by_constant(c.c1);
+    let tv2 = one.add(&tv1);
+    let tv1 = one.sub(&tv1);
+    let tv3 = Fq2Var::div_witness(&one, &tv1.mul(&tv2)?)?;
+    let tv4 = u.mul(&tv1)?.mul(&tv3)?.mul_by_constant(c.c3);
+    let c2 = Fq2Var::constant(c.c2);
+    let x1 = c2.sub(&tv4);
+    let x2 = c2.add(&tv4);
+    let x3 = tv2
+        .square()?
+        .mul(&tv3)?
+        .square()?
+        .mul_by_constant(c.c4)
+        .add(&Fq2Var::constant(c.z));
+    let e1 = is_square_var(&g_var(&x1)?)?;
+    let e2 = is_square_var(&g_var(&x2)?)?;
+    let x = Fq2Var::conditionally_select(&e1, &x1, &Fq2Var::conditionally_select(&e2, &x2, &x3)?)?;
+    let y = Fq2Var::new_witness(ns!(cs, "y"), || {
+        let y = g(x.value()?).sqrt().unwrap();
+        Ok(if y.sgn0() != u.value()?.sgn0() { -y } else { y })
+    })?;
+    y.square()?.enforce_equal(&g_var(&x)?)?;
+    y.sgn0()?.enforce_equal(&u.sgn0()?)?;
+    Ok(G2AffineVar::new(x, y))
+}
+
+/// Bits kept from each squeezed element, all but the top one. Two chunks span
+/// more bits than Fq and their packing is reduced modulo its size.
+fn chunk_bits<CF: PrimeField>() -> usize {
+    CF::size_in_bits() - 1
+}
+
+fn low_bits_to_fq<CF: PrimeField>(x: CF) -> Fq {
+    let bits = x.into_repr().to_bits_le();
+    Fq::from_repr(BigInteger::from_bits_le(&bits[..chunk_bits::<CF>()])).unwrap()
+}
+
+/// Absorbs the message in a Poseidon sponge over the constraint field and
+/// squeezes `n` Fq2 elements, each Fq coordinate packing two squeezed
+/// elements as lo + 2^k hi.
+pub fn hash_to_fq2<CF: PrimeField + Absorb>(
+    params: &PoseidonParameters<CF>,
+    msg: &[CF],
+    n: usize,
+) -> Vec<Fq2> {
+    let shift = Fq::from(2u64).pow([chunk_bits::<CF>() as u64]);
+    let fq = hash_to_field(params, G2_DOMAIN, msg, 4 * n)
+        .chunks(2)
+        .map(|c| low_bits_to_fq(c[0]) + low_bits_to_fq(c[1]) * shift)
+        .collect::<Vec<_>>();
+    fq.chunks(2).map(|c| Fq2::new(c[0], c[1])).collect()
+}
+
+pub fn hash_to_fq2_var<CF: PrimeField>(
+    params: &PoseidonParameters<CF>,
+    msg: &[FpVar<CF>],
+    n: usize,
+) -> Result<Vec<Fq2Var<CF>>, SynthesisError> {
+    let k = chunk_bits::<CF>();
+    let shift = Fq::from(2u64).pow([k as u64]);
+    let fq = hash_to_field_var(params, G2_DOMAIN, msg, 4 * n)?
+        .chunks(2)
+        .map(|c| {
+            let lo = fq_from_bits_le(&c[0].to_bits_le()?[..k])?;
+            let hi = fq_from_bits_le(&c[1].to_bits_le()?[..k])?;
+            Ok(lo + hi * shift)
+        })
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+    Ok(fq
+        .chunks(2)
+        .map(|c| Fq2Var::new(c[0].clone(), c[1].clone()))
+        .collect())
+}
+
+impl PsiParameters for Parameters {
+    #[rustfmt::skip]
+    const PSI_X: Fq2 = field_new!(Fq2, field_new!(Fq, "0"), field_new!(Fq, "4002409555221667392624310435006688643935503118305586438271171395842971157480381377015405980053539358417135540939437"));
+    #[rustfmt::skip]
+    const PSI_Y: Fq2 = field_new!(Fq2, field_new!(Fq, "2973677408986561043442465346520108879172042883009249989176415018091420807192182638567116318576472649347015917690530"), field_new!(Fq, "1028732146235106349975324479215795277384839936929757896155643118032610843298655225875571310552543014690878354869257"));
+}
+
+pub fn psi_var<CF: PrimeField>(p: &G2AffineVar<CF>) -> Result<G2AffineVar<CF>, SynthesisError> {
+    Ok(G2AffineVar::new(
+        p.x.frobenius_map(1)?.mul_by_constant(Parameters::PSI_X),
+        p.y.frobenius_map(1)?.mul_by_constant(Parameters::PSI_Y),
+    ))
+}
+
+/// Enforces that `p` is in G2 with psi(P) = [x]P, which on the twist holds
+/// exactly for the points of G2 (https://eprint.iacr.org/2021/1130).
+pub fn enforce_in_g2<CF: PrimeField>(p: &G2AffineVar<CF>) -> Result<(), SynthesisError> {
+    psi_var(p)?.enforce_equal(&mul_by_x_var(p)?)
+}
+
+fn mul_by_x_var<CF: PrimeField>(p: &G2AffineVar<CF>) -> Result<G2AffineVar<CF>, SynthesisError> {
+    let x_p = p.mul_by_constant(Parameters::X)?;
+    if Parameters::X_IS_NEGATIVE {
+        x_p.negate()
+    } else {
+        Ok(x_p)
+    }
+}
+
+/// R1CS version of [`clear_cofactor_g2`] with incomplete affine additions, whose
+/// operands are distinct except with negligible probability.
+pub fn clear_cofactor_var<CF: PrimeField>(
+    p: &G2AffineVar<CF>,
+) -> Result<G2AffineVar<CF>, SynthesisError> {
+    let x_p = mul_by_x_var(p)?;
+    let psi_p = psi_var(p)?;
+    let psi2_2p = psi_var(&psi_var(&p.double()?)?)?;
+    let x2_p_x_psi_p = mul_by_x_var(&x_p.add(&psi_p)?)?;
+    psi2_2p
+        .add(&x2_p_x_psi_p)?
+        .add(&x_p.negate()?)?
+        .add(&psi_p.negate()?)?
+        .add(&p.negate()?)
+}
+
+pub fn hash_to_g2<CF: PrimeField + Absorb>(
+    params: &PoseidonParameters<CF>,
+    msg: &[CF],
+) -> G2Affine {
+    let u = hash_to_fq2(params, msg, 2);
+    let q = map_to_curve(u[0]).into_projective() + map_to_curve(u[1]).into_projective();
+    clear_cofactor_g2::<Parameters>(&q).into_affine()
+}
+
+pub fn hash_to_g2_var<CF: PrimeField>(
+    params: &PoseidonParameters<CF>,
+    msg: &[FpVar<CF>],
+) -> Result<G2AffineVar<CF>, SynthesisError> {
+    let u = hash_to_fq2_var(params, msg, 2)?;
+    let q = map_to_curve_var(&u[0])?.add(&map_to_curve_var(&u[1])?)?;
+    clear_cofactor_var(&q)
+}
+
+/// A point of order 13 on the twist, outside G2.
+#[cfg(test)]
+pub(crate) fn small_order_point() -> G2Affine {
+    use ark_std::UniformRand;
+    use num_bigint::BigUint;
+    let mut rng = ark_std::test_rng();
+    let big = |limbs: &[u64]| {
+        BigUint::from_bytes_le(
+            &limbs
+                .iter()
+                .flat_map(|l| l.to_le_bytes())
+                .collect::<Vec<_>>(),
+        )
+    };
+    // the 13-torsion of the twist has exponent 13 and the cofactor is
+    // divisible by 13^2
+    let n = big(g2::Parameters::COFACTOR) / 169u32 * big(ark_bls12_381::Fr::characteristic());
+    let t = loop {
+        let t = map_to_curve(Fq2::rand(&mut rng))
+            .into_projective()
+            .mul(n.to_u64_digits());
+        if !t.is_zero() {
+            break t.into_affine();
+        }
+    };
+    assert!(t.mul(13u64).is_zero());
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_to_curve::{mul_by_x, psi};
+    use crate::poseidon::get_bls12377_fq_params;
+    use ark_bls12_381::G2Projective;
+    use ark_r1cs_std::alloc::AllocVar;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::UniformRand;
+
+    type CF = ark_bls12_377::Fq;
+
+    #[test]
+    fn native_hash_to_g2() {
+        let mut rng = ark_std::test_rng();
+        println!("svdw z = {}", SVDW.z);
+        for _ in 0..8 {
+            assert!(map_to_curve(Fq2::rand(&mut rng)).is_on_curve());
+        }
+
+        // psi acts as multiplication by x on G2
+        let g = G2Projective::prime_subgroup_generator();
+        assert_eq!(psi::<Parameters>(&g), mul_by_x::<Parameters>(&g));
+
+        let params = get_bls12377_fq_params(2);
+        let h = hash_to_g2(&params, &[CF::from(1u64)]);
+        assert!(h.is_on_curve() && h.is_in_correct_subgroup_assuming_on_curve());
+        assert!(!h.is_zero());
+        assert_ne!(h, hash_to_g2(&params, &[CF::from(2u64)]));
+    }
+
+    /// The gadgets against their native versions. The full cofactor clearing
+    /// is ~130 emulated G2 operations, so only its pieces are synthesized.
+    #[test]
+    fn hash_to_g2_gadgets() {
+        let mut rng = ark_std::test_rng();
+        let params = get_bls12377_fq_params(2);
+        let msg = [CF::from(7u64), CF::from(11u64)];
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let msg_var = Vec::<FpVar<CF>>::new_witness(ns!(cs, "msg"), || Ok(msg.to_vec())).unwrap();
+        let u = hash_to_fq2_var(&params, &msg_var, 2).unwrap();
+        let expected = hash_to_fq2(&params, &msg, 2);
+        assert_eq!(u[0].value().unwrap(), expected[0]);
+        assert_eq!(u[1].value().unwrap(), expected[1]);
+        println!("hash to fq2: {} constraints", cs.num_constraints());
+
+        let before = cs.num_constraints();
+        let q = map_to_curve_var(&u[0]).unwrap();
+        assert_eq!(q.value().unwrap(), map_to_curve(expected[0]));
+        println!("svdw map: {} constraints", cs.num_constraints() - before);
+
+        let p = G2Projective::rand(&mut rng);
+        let p_var = G2AffineVar::new_witness(ns!(cs, "p"), || Ok(p.into_affine())).unwrap();
+        assert_eq!(
+            psi_var(&p_var).unwrap().value().unwrap(),
+            psi::<Parameters>(&p).into_affine()
+        );
+        assert_eq!(
+            p_var.mul_by_constant(&[0b1011]).unwrap().value().unwrap(),
+            p.mul([0b1011]).into_affine()
+        );
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    /// Multiplication by 15 = 0b1111 reaches 14T = T before its last
+    /// addition when T has order 13: the chord slope of T + T would be free,
+    /// so the constraints must not be satisfiable.
+    #[test]
+    fn mul_by_constant_small_order() {
+        let mut rng = ark_std::test_rng();
+        let p = G2Projective::rand(&mut rng);
+        for (q, expected) in [
+            (p.into_affine(), Some(p.mul([15]).into_affine())),
+            (small_order_point(), None),
+        ] {
+            let cs = ConstraintSystem::<CF>::new_ref();
+            let q_var = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(q)).unwrap();
+            let res = q_var.mul_by_constant(&[15]).unwrap();
+            if let Some(expected) = expected {
+                assert_eq!(res.value().unwrap(), expected);
+            }
+            assert_eq!(cs.is_satisfied().unwrap(), expected.is_some());
+        }
+    }
+
+    /// Points of G2 pass and the other points of the twist fail, including
+    /// one of small order. Each check is about 70 emulated G2 operations and
+    /// needs far more memory than the other tests.
+    #[test]
+    #[ignore]
+    fn g2_membership() {
+        let mut rng = ark_std::test_rng();
+        for (p, in_g2) in [
+            (G2Projective::rand(&mut rng).into_affine(), true),
+            (map_to_curve(Fq2::rand(&mut rng)), false),
+            (small_order_point(), false),
+        ] {
+            let cs = ConstraintSystem::<CF>::new_ref();
+            let p_var = G2AffineVar::new_witness(ns!(cs, "p"), || Ok(p)).unwrap();
+            let satisfied = enforce_in_g2(&p_var).is_ok() && cs.is_satisfied().unwrap();
+            assert_eq!(satisfied, in_g2);
+            println!("G2 membership: {} constraints", cs.num_constraints());
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! BLS12-381 pairing emulated inside the scalar field of another curve, e.g.
+//! BLS12-377 Fr or BW6-761 Fr (= BLS12-377 Fq).
+pub mod curves;
+pub mod fields;
+pub mod hash_to_curve;
+pub mod pairing;
+
+use super::OpMode;
+use ark_bls12_381::{Bls12_381, Fq12, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::PrimeField;
+use ark_r1cs_std::alloc::AllocVar;
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use ark_std::{
+    marker::PhantomData,
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+use curves::{G1AffineVar, G2AffineVar};
+use fields::Fq12Var;
+
+struct EmulatedCircuit<CF: PrimeField> {
+    p: G1Affine,
+    q: G2Affine,
+    a: Fq12,
+    b: Fq12,
+    mode: OpMode,
+    _cf: PhantomData<CF>,
+}
+
+impl<CF: PrimeField> EmulatedCircuit<CF> {
+    #[allow(dead_code)]
+    pub fn new<R: Rng + CryptoRng>(rng: &mut R, mode: OpMode) -> Self {
+        Self {
+            p: G1Projective::rand(rng).into_affine(),
+            q: G2Projective::rand(rng).into_affine(),
+            a: Fq12::rand(rng),
+            b: Fq12::rand(rng),
+            mode,
+            _cf: PhantomData,
+        }
+    }
+}
+
+impl<CF: PrimeField> ConstraintSynthesizer<CF> for EmulatedCircuit<CF> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<CF>) -> Result<(), SynthesisError> {
+        match self.mode {
+            OpMode::EmulatedFq12Mul => {
+                let a = Fq12Var::new_witness(ns!(cs, "a"), || Ok(self.a))?;
+                let b = Fq12Var::new_witness(ns!(cs, "b"), || Ok(self.b))?;
+                let c = Fq12Var::new_witness(ns!(cs, "c"), || Ok(self.a * self.b))?;
+                a.mul(&b)?.enforce_equal(&c)?;
+            }
+            OpMode::EmulatedG1Add => {
+                let p = G1AffineVar::new_witness(ns!(cs, "p"), || Ok(self.p))?;
+                let p2 = G1AffineVar::new_witness(ns!(cs, "2p"), || {
+                    Ok(self.p.into_projective().double().into_affine())
+                })?;
+                let p3 =
+                    G1AffineVar::new_witness(ns!(cs, "3p"), || Ok(self.p.mul(3u64).into_affine()))?;
+                p.add(&p2)?.enforce_equal(&p3)?;
+            }
+            OpMode::EmulatedG2Add => {
+                let q = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(self.q))?;
+                let q2 = G2AffineVar::new_witness(ns!(cs, "2q"), || {
+                    Ok(self.q.into_projective().double().into_affine())
+                })?;
+                let q3 =
+                    G2AffineVar::new_witness(ns!(cs, "3q"), || Ok(self.q.mul(3u64).into_affine()))?;
+                q.add(&q2)?.enforce_equal(&q3)?;
+            }
+            OpMode::EmulatedMillerLoop(n) => {
+                let ps = (0..n)
+                    .map(|_| G1AffineVar::new_witness(ns!(cs, "p"), || Ok(self.p)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let qs = (0..n)
+                    .map(|_| G2AffineVar::new_witness(ns!(cs, "q"), || Ok(self.q)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                pairing::miller_loop(&ps, &qs)?;
+            }
+            OpMode::EmulatedFinalExp => {
+                let f = Fq12Var::new_witness(ns!(cs, "f"), || {
+                    Ok(Bls12_381::miller_loop(&[(self.p.into(), self.q.into())]))
+                })?;
+                pairing::final_exponentiation(&f)?;
+            }
+            OpMode::EmulatedPairing => {
+                let p = G1AffineVar::new_witness(ns!(cs, "p"), || Ok(self.p))?;
+                let q = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(self.q))?;
+                let e =
+                    Fq12Var::new_witness(ns!(cs, "e"), || Ok(Bls12_381::pairing(self.p, self.q)))?;
+                pairing::pairing(&p, &q)?.enforce_equal(&e)?;
+            }
+            _ => panic!("unsupported operation on emulated bls12-381 circuit"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::{Fq, Fq2, Fq6};
+    use ark_ff::{BigInteger, Field, One, Zero};
+    use ark_nonnative_field::{
+        params::{get_params, OptimizationType},
+        NonNativeFieldVar,
+    };
+    use ark_r1cs_std::{eq::EqGadget, R1CSVar};
+    use ark_relations::r1cs::{ConstraintSystem, SynthesisMode};
+    use curves::AffineVar;
+    use fields::{Fq2Var, Fq6Var, FqVar};
+    use num_bigint::BigUint;
+    use std::ops::Neg;
+
+    type CF = ark_bls12_377::Fr;
+
+    /// Maps a random element to the cyclotomic subgroup with the easy part
+    /// of the final exponentiation.
+    fn cyclotomic(f: Fq12) -> Fq12 {
+        let mut c = f;
+        c.conjugate();
+        let r = c * f.inverse().unwrap();
+        let mut r2 = r;
+        r2.frobenius_map(2);
+        r2 * r
+    }
+
+    #[test]
+    fn emulated_tower() {
+        let mut rng = ark_std::test_rng();
+        let a = Fq12::rand(&mut rng);
+        let b = Fq12::rand(&mut rng);
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let av = Fq12Var::new_witness(ns!(cs, "a"), || Ok(a)).unwrap();
+        let bv = Fq12Var::new_witness(ns!(cs, "b"), || Ok(b)).unwrap();
+        assert_eq!(av.mul(&bv).unwrap().value().unwrap(), a * b);
+        assert_eq!(av.square().unwrap().value().unwrap(), a.square());
+        assert_eq!(av.inverse().unwrap().value().unwrap(), a.inverse().unwrap());
+        for power in 1..4 {
+            let mut expected = a;
+            expected.frobenius_map(power);
+            assert_eq!(av.frobenius_map(power).unwrap().value().unwrap(), expected);
+        }
+
+        let (c0, c1, c4) = (
+            Fq2::rand(&mut rng),
+            Fq2::rand(&mut rng),
+            Fq2::rand(&mut rng),
+        );
+        let mut expected = a;
+        expected.mul_by_014(&c0, &c1, &c4);
+        let line = [c0, c1, c4]
+            .iter()
+            .map(|c| Fq2Var::new_witness(ns!(cs, "line"), || Ok(c)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            av.mul_by_014(&line[0], &line[1], &line[2])
+                .unwrap()
+                .value()
+                .unwrap(),
+            expected
+        );
+
+        let a6 = Fq6Var::new_witness(ns!(cs, "a6"), || Ok(a.c0)).unwrap();
+        assert_eq!(a6.square().unwrap().value().unwrap(), a.c0.square());
+        assert_eq!(
+            a6.mul_by_fq2(&line[0]).unwrap().value().unwrap(),
+            a.c0 * Fq6::new(c0, Fq2::zero(), Fq2::zero())
+        );
+        assert_eq!(
+            fields::EmulatedFieldVar::value(&Fq2Var::<CF>::zero()).unwrap(),
+            Fq2::zero()
+        );
+
+        let c = cyclotomic(a);
+        let cv = Fq12Var::new_witness(ns!(cs, "c"), || Ok(c)).unwrap();
+        assert_eq!(
+            cv.cyclotomic_square().unwrap().value().unwrap(),
+            c.cyclotomic_square()
+        );
+        assert_eq!(
+            cv.cyclotomic_exp(&[0x1234]).unwrap().value().unwrap(),
+            c.pow([0x1234u64])
+        );
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn emulated_curves() {
+        let mut rng = ark_std::test_rng();
+        let p = G1Projective::rand(&mut rng);
+        let q = G2Projective::rand(&mut rng);
+        let r = G2Projective::rand(&mut rng);
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let pv = G1AffineVar::new_witness(ns!(cs, "p"), || Ok(p.into_affine())).unwrap();
+        let qv = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(q.into_affine())).unwrap();
+        let rv = G2AffineVar::new_witness(ns!(cs, "r"), || Ok(r.into_affine())).unwrap();
+        assert_eq!(
+            pv.double().unwrap().value().unwrap(),
+            p.double().into_affine()
+        );
+        assert_eq!(qv.add(&rv).unwrap().value().unwrap(), (q + r).into_affine());
+        assert_eq!(
+            qv.double().unwrap().value().unwrap(),
+            q.double().into_affine()
+        );
+        assert_eq!(pv.negate().unwrap().value().unwrap(), p.into_affine().neg());
+        assert!(cs.is_satisfied().unwrap());
+
+        // a point off the curve
+        let cs = ConstraintSystem::<CF>::new_ref();
+        AffineVar::<_, fields::FqVar<CF>, CF>::new_witness(ns!(cs, "p"), || {
+            Ok(G1Affine::new(Fq::one(), Fq::one(), false))
+        })
+        .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    /// Whether every limb of `x` is below (counter + 1) times its bound in the
+    /// normal form, which the reductions of ark-nonnative rely on.
+    fn counter_bounds_limbs(x: &FqVar<CF>) -> bool {
+        let x = match x {
+            NonNativeFieldVar::Var(x) => x,
+            NonNativeFieldVar::Constant(_) => return true,
+        };
+        let params = get_params(
+            Fq::size_in_bits(),
+            CF::size_in_bits(),
+            OptimizationType::Constraints,
+        );
+        let top_bits = Fq::size_in_bits() - params.bits_per_limb * (params.num_limbs - 1);
+        let to_big = |v: CF| BigUint::from_bytes_le(&v.into_repr().to_bytes_le());
+        let bound = to_big(x.num_of_additions_over_normal_form) + 1u32;
+        // limbs are stored most significant first
+        x.limbs.iter().enumerate().all(|(i, limb)| {
+            let bits = if i == 0 {
+                top_bits
+            } else {
+                params.bits_per_limb
+            };
+            to_big(limb.value().unwrap()) < (&bound << bits)
+        })
+    }
+
+    /// Subtraction chains whose subtrahends carry ever larger addition
+    /// counters, until every reduction path of `sub_fq` is taken. Each step
+    /// is multiplied and checked against the native value: the limb
+    /// equalities of the products only hold if the counters bound the limbs,
+    /// which is also checked directly, and the tampered run checks that a
+    /// wrong product is still caught.
+    fn sub_chain(steps: usize, tampered: Option<usize>) -> bool {
+        let mut rng = ark_std::test_rng();
+        let (a, b) = (Fq::rand(&mut rng), Fq::rand(&mut rng));
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let av = FqVar::new_witness(ns!(cs, "a"), || Ok(a)).unwrap();
+        let bv = FqVar::new_witness(ns!(cs, "b"), || Ok(b)).unwrap();
+        let (mut x, mut xv) = (b, bv.clone());
+        for i in 0..steps {
+            // alternately a large subtrahend and a large minuend
+            if i % 3 == 2 {
+                x -= a;
+                xv = fields::sub_fq(&xv, &av);
+            } else {
+                x = a - x.double();
+                xv = fields::sub_fq(&av, &(&xv + &xv));
+            }
+            assert!(counter_bounds_limbs(&xv));
+            let expected = if tampered == Some(i) {
+                x * b + Fq::one()
+            } else {
+                x * b
+            };
+            let ev = FqVar::new_witness(ns!(cs, "expected"), || Ok(expected)).unwrap();
+            (&xv * &bv).enforce_equal(&ev).unwrap();
+        }
+        assert_eq!(xv.value().unwrap(), x);
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn emulated_sub_chains() {
+        // the subtraction of ark-nonnative undercounts its padding
+        let mut rng = ark_std::test_rng();
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let av = FqVar::new_witness(ns!(cs, "a"), || Ok(Fq::rand(&mut rng))).unwrap();
+        let bv = FqVar::new_witness(ns!(cs, "b"), || Ok(Fq::rand(&mut rng))).unwrap();
+        assert!(!counter_bounds_limbs(&(&av - &bv)));
+        assert!(counter_bounds_limbs(&fields::sub_fq(&av, &bv)));
+
+        assert!(sub_chain(100, None));
+        for i in [0, 45, 99] {
+            assert!(!sub_chain(i + 1, Some(i)));
+        }
+    }
+
+    fn bench<CF: PrimeField>(name: &str) {
+        let mut rng = ark_std::test_rng();
+        for mode in [
+            OpMode::EmulatedFq12Mul,
+            OpMode::EmulatedG1Add,
+            OpMode::EmulatedG2Add,
+        ] {
+            let cs = ConstraintSystem::<CF>::new_ref();
+            EmulatedCircuit::<CF>::new(&mut rng, mode.clone())
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!(
+                "{:?} over {}: {} constraints",
+                mode,
+                name,
+                cs.num_constraints()
+            );
+        }
+    }
+
+    #[test]
+    fn bench_emulated() {
+        bench::<ark_bls12_377::Fr>("BLS12-377 Fr");
+        bench::<ark_bls12_377::Fq>("BLS12-377 Fq");
+    }
+
+    /// Counts only: the pairing modes are millions of constraints, so they
+    /// are synthesized without the matrices, which the satisfiability checks
+    /// of `pairing::tests` cover on smaller pieces.
+    fn bench_pairing<CF: PrimeField>(name: &str) {
+        let mut rng = ark_std::test_rng();
+        for mode in [
+            OpMode::EmulatedMillerLoop(1),
+            OpMode::EmulatedMillerLoop(2),
+            OpMode::EmulatedFinalExp,
+            OpMode::EmulatedPairing,
+        ] {
+            let cs = ConstraintSystem::<CF>::new_ref();
+            cs.set_mode(SynthesisMode::Prove {
+                construct_matrices: false,
+            });
+            EmulatedCircuit::<CF>::new(&mut rng, mode.clone())
+                .generate_constraints(cs.clone())
+                .unwrap();
+            println!(
+                "{:?} over {}: {} constraints",
+                mode,
+                name,
+                cs.num_constraints()
+            );
+        }
+    }
+
+    /// Needs far more memory than the other tests, like `emulated_pairing`.
+    #[test]
+    #[ignore]
+    fn bench_emulated_pairing() {
+        bench_pairing::<ark_bls12_377::Fr>("BLS12-377 Fr");
+        bench_pairing::<ark_bls12_377::Fq>("BLS12-377 Fq");
+    }
+
+    /// Full Miller loop and final exponentiation: tens of millions of
+    /// constraints, so synthesis runs without building the matrices and only
+    /// the output value is checked against the native pairing. Even then the
+    /// linear combinations take far more memory than the other tests.
+    #[test]
+    #[ignore]
+    fn emulated_pairing() {
+        let mut rng = ark_std::test_rng();
+        let p = G1Projective::rand(&mut rng).into_affine();
+        let q = G2Projective::rand(&mut rng).into_affine();
+        let cs = ConstraintSystem::<CF>::new_ref();
+        cs.set_mode(SynthesisMode::Prove {
+            construct_matrices: false,
+        });
+        let pv = G1AffineVar::new_witness(ns!(cs, "p"), || Ok(p)).unwrap();
+        let qv = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(q)).unwrap();
+        let ml = pairing::miller_loop(&[pv], &[qv]).unwrap();
+        let native_ml = Bls12_381::miller_loop(&[(p.into(), q.into())]);
+        assert_eq!(ml.value().unwrap(), native_ml);
+        println!("emulated miller loop: {} constraints", cs.num_constraints());
+        let before = cs.num_constraints();
+        let e = pairing::final_exponentiation(&ml).unwrap();
+        assert_eq!(e.value().unwrap(), Bls12_381::pairing(p, q));
+        println!(
+            "emulated final exponentiation: {} constraints",
+            cs.num_constraints() - before
+        );
+    }
+}
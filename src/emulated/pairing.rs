@@ -0,0 +1,334 @@
+//! Optimal ate pairing of BLS12-381 over emulated fields, following the
+//! native implementation of ark-ec: homogeneous projective G2 arithmetic with
+//! sparse line multiplications in the Miller loop and the final
+//! exponentiation of https://eprint.iacr.org/2020/875.
+use super::{
+    curves::{G1AffineVar, G2AffineVar},
+    fields::{EmulatedFieldVar, Fq12Var, Fq2Var, FqVar},
+};
+use ark_bls12_381::{g2, Fq, Parameters};
+use ark_ec::{bls12::Bls12Parameters, SWModelParameters};
+use ark_ff::{BitIteratorBE, Field, PrimeField};
+use ark_relations::r1cs::SynthesisError;
+
+/// Coefficients of a line evaluated at the G1 point by [`ell`].
+type LineCoeffs<CF> = (Fq2Var<CF>, Fq2Var<CF>, Fq2Var<CF>);
+
+/// Running G2 point of the Miller loop in homogeneous projective coordinates.
+#[derive(Clone)]
+struct G2ProjectiveVar<CF: PrimeField> {
+    x: Fq2Var<CF>,
+    y: Fq2Var<CF>,
+    z: Fq2Var<CF>,
+}
+
+impl<CF: PrimeField> G2ProjectiveVar<CF> {
+    fn doubling_step(&mut self) -> Result<LineCoeffs<CF>, SynthesisError> {
+        let two_inv = FqVar::<CF>::constant(Fq::from(2u64).inverse().unwrap());
+        let a = self.x.mul(&self.y)?.mul_by_fp(&two_inv);
+        let b = self.y.square()?;
+        let c = self.z.square()?;
+        let e = c.double()?.add(&c).mul_by_constant(g2::Parameters::COEFF_B);
+        let f = e.double()?.add(&e);
+        let g = b.add(&f).mul_by_fp(&two_inv);
+        let h = self.y.add(&self.z).square()?.sub(&b.add(&c));
+        let i = e.sub(&b);
+        let j = self.x.square()?;
+        let e_square = e.square()?;
+
+        self.x = a.mul(&b.sub(&f))?;
+        self.y = g.square()?.sub(&e_square.double()?.add(&e_square));
+        self.z = b.mul(&h)?;
+        Ok((i, j.double()?.add(&j), h.negate()?))
+    }
+
+    fn addition_step(&mut self, q: &G2AffineVar<CF>) -> Result<LineCoeffs<CF>, SynthesisError> {
+        let theta = self.y.sub(&q.y.mul(&self.z)?);
+        let lambda = self.x.sub(&q.x.mul(&self.z)?);
+        let c = theta.square()?;
+        let d = lambda.square()?;
+        let e = lambda.mul(&d)?;
+        let f = self.z.mul(&c)?;
+        let g = self.x.mul(&d)?;
+        let h = e.add(&f).sub(&g.double()?);
+        self.x = lambda.mul(&h)?;
+        self.y = theta.mul(&g.sub(&h))?.sub(&e.mul(&self.y)?);
+        self.z = self.z.mul(&e)?;
+        let j = theta.mul(&q.x)?.sub(&lambda.mul(&q.y)?);
+        Ok((j, theta.negate()?, lambda))
+    }
+}
+
+/// Multiplies `f` by the line with coefficients `coeffs` evaluated at `p`.
+fn ell<CF: PrimeField>(
+    f: &Fq12Var<CF>,
+    coeffs: &LineCoeffs<CF>,
+    p: &G1AffineVar<CF>,
+) -> Result<Fq12Var<CF>, SynthesisError> {
+    let c1 = coeffs.1.mul_by_fp(&p.x);
+    let c2 = coeffs.2.mul_by_fp(&p.y);
+    f.mul_by_014(&coeffs.0, &c1, &c2)
+}
+
+/// Multi Miller loop over non-zero pairs, sharing the squarings of `f`.
+pub fn miller_loop<CF: PrimeField>(
+    ps: &[G1AffineVar<CF>],
+    qs: &[G2AffineVar<CF>],
+) -> Result<Fq12Var<CF>, SynthesisError> {
+    let bits = BitIteratorBE::new(Parameters::X).skip_while(|b| !b).skip(1);
+    let f = miller_loop_bits(ps, qs, bits)?;
+    if Parameters::X_IS_NEGATIVE {
+        f.conjugate()
+    } else {
+        Ok(f)
+    }
+}
+
+/// The Miller loop body for the given bits of the loop count, below its
+/// leading one.
+pub(crate) fn miller_loop_bits<CF: PrimeField>(
+    ps: &[G1AffineVar<CF>],
+    qs: &[G2AffineVar<CF>],
+    bits: impl Iterator<Item = bool>,
+) -> Result<Fq12Var<CF>, SynthesisError> {
+    assert_eq!(ps.len(), qs.len());
+    let mut rs = qs
+        .iter()
+        .map(|q| G2ProjectiveVar {
+            x: q.x.clone(),
+            y: q.y.clone(),
+            z: Fq2Var::one(),
+        })
+        .collect::<Vec<_>>();
+    let mut f = Fq12Var::one();
+    for bit in bits {
+        f = f.square()?;
+        for (p, r) in ps.iter().zip(rs.iter_mut()) {
+            let coeffs = r.doubling_step()?;
+            f = ell(&f, &coeffs, p)?;
+        }
+        if bit {
+            for ((p, q), r) in ps.iter().zip(qs).zip(rs.iter_mut()) {
+                let coeffs = r.addition_step(q)?;
+                f = ell(&f, &coeffs, p)?;
+            }
+        }
+    }
+    Ok(f)
+}
+
+/// f^x for f in the cyclotomic subgroup, x being the signed curve parameter.
+fn exp_by_x<P: Bls12Parameters, CF: PrimeField>(
+    f: &Fq12Var<CF>,
+) -> Result<Fq12Var<CF>, SynthesisError> {
+    let res = f.cyclotomic_exp(P::X)?;
+    if P::X_IS_NEGATIVE {
+        res.conjugate()
+    } else {
+        Ok(res)
+    }
+}
+
+pub fn final_exponentiation<CF: PrimeField>(
+    f: &Fq12Var<CF>,
+) -> Result<Fq12Var<CF>, SynthesisError> {
+    hard_part::<Parameters, CF>(&easy_part(f)?)
+}
+
+/// f^((p^6 - 1)(p^2 + 1)), which lands in the cyclotomic subgroup.
+pub(crate) fn easy_part<CF: PrimeField>(f: &Fq12Var<CF>) -> Result<Fq12Var<CF>, SynthesisError> {
+    let f1 = f.conjugate()?;
+    let f2 = f.inverse()?;
+    let r = f1.mul(&f2)?;
+    let f2 = r.clone();
+    r.frobenius_map(2)?.mul(&f2)
+}
+
+/// The hard part for the curve parameter of `P`, whose tower must be the one
+/// of BLS12-381; only BLS12-381 itself gives the pairing.
+pub(crate) fn hard_part<P: Bls12Parameters, CF: PrimeField>(
+    r: &Fq12Var<CF>,
+) -> Result<Fq12Var<CF>, SynthesisError> {
+    let y0 = r.cyclotomic_square()?;
+    let y1 = exp_by_x::<P, CF>(r)?;
+    let y2 = r.conjugate()?;
+    let y1 = y1.mul(&y2)?;
+    let y2 = exp_by_x::<P, CF>(&y1)?;
+    let y1 = y1.conjugate()?.mul(&y2)?;
+    let y2 = exp_by_x::<P, CF>(&y1)?;
+    let y1 = y1.frobenius_map(1)?.mul(&y2)?;
+    let r = r.mul(&y0)?;
+    let y0 = exp_by_x::<P, CF>(&y1)?;
+    let y2 = exp_by_x::<P, CF>(&y0)?;
+    let y0 = y1.frobenius_map(2)?;
+    let y1 = y1.conjugate()?.mul(&y2)?.mul(&y0)?;
+    r.mul(&y1)
+}
+
+pub fn pairing<CF: PrimeField>(
+    p: &G1AffineVar<CF>,
+    q: &G2AffineVar<CF>,
+) -> Result<Fq12Var<CF>, SynthesisError> {
+    final_exponentiation(&miller_loop(
+        std::slice::from_ref(p),
+        std::slice::from_ref(q),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::{Fq12, G1Projective, G2Projective};
+    use ark_ec::{
+        bls12::{Bls12, G2Prepared, TwistType},
+        PairingEngine, ProjectiveCurve,
+    };
+    use ark_ff::One;
+    use ark_r1cs_std::alloc::AllocVar;
+    use ark_relations::{ns, r1cs::ConstraintSystem};
+    use ark_std::UniformRand;
+
+    type CF = ark_bls12_377::Fr;
+    type Fq377 = ark_bls12_377::Fq;
+
+    /// BLS12-381 with the curve parameter -2: the hard part of the final
+    /// exponentiation keeps its structure at a fraction of its size.
+    struct ShortX;
+
+    impl Bls12Parameters for ShortX {
+        const X: &'static [u64] = &[2];
+        const X_IS_NEGATIVE: bool = true;
+        const TWIST_TYPE: TwistType = TwistType::M;
+        type Fp = Fq;
+        type Fp2Params = <Parameters as Bls12Parameters>::Fp2Params;
+        type Fp6Params = <Parameters as Bls12Parameters>::Fp6Params;
+        type Fp12Params = <Parameters as Bls12Parameters>::Fp12Params;
+        type G1Parameters = <Parameters as Bls12Parameters>::G1Parameters;
+        type G2Parameters = <Parameters as Bls12Parameters>::G2Parameters;
+    }
+
+    /// The first iterations of the loop against the line coefficients that
+    /// ark-ec precomputes for the same point; the whole loop and the final
+    /// exponentiation are too large to synthesize here.
+    #[test]
+    fn miller_loop_steps() {
+        let mut rng = ark_std::test_rng();
+        let p = G1Projective::rand(&mut rng).into_affine();
+        let q = G2Projective::rand(&mut rng).into_affine();
+        let bits = BitIteratorBE::new(Parameters::X)
+            .skip_while(|b| !b)
+            .skip(1)
+            .take(3)
+            .collect::<Vec<_>>();
+
+        let prepared = G2Prepared::<Parameters>::from(q);
+        let mut coeffs = prepared.ell_coeffs.iter();
+        let mut ell = |f: &mut Fq12| {
+            let (c0, mut c1, mut c2) = *coeffs.next().unwrap();
+            c1.mul_assign_by_fp(&p.x);
+            c2.mul_assign_by_fp(&p.y);
+            f.mul_by_014(&c0, &c1, &c2);
+        };
+        let mut expected = Fq12::one();
+        for bit in bits.iter() {
+            expected.square_in_place();
+            ell(&mut expected);
+            if *bit {
+                ell(&mut expected);
+            }
+        }
+
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let pv = G1AffineVar::new_witness(ns!(cs, "p"), || Ok(p)).unwrap();
+        let qv = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(q)).unwrap();
+        let f = miller_loop_bits(&[pv], &[qv], bits.into_iter()).unwrap();
+        assert_eq!(f.value().unwrap(), expected);
+        assert!(cs.is_satisfied().unwrap());
+        println!(
+            "3 miller loop iterations: {} constraints",
+            cs.num_constraints()
+        );
+    }
+
+    /// The easy and the hard part of the final exponentiation, each in its
+    /// own constraint system, against ark-ec for [`ShortX`]: the full
+    /// exponentiation is millions of constraints. BLS12-377 Fq packs the
+    /// emulated limbs in fewer constraints than Fr, which keeps the hard part
+    /// within memory.
+    #[test]
+    fn final_exponentiation_parts() {
+        let mut rng = ark_std::test_rng();
+        let f = Fq12::rand(&mut rng);
+        let r = {
+            let cs = ConstraintSystem::<Fq377>::new_ref();
+            let fv = Fq12Var::new_witness(ns!(cs, "f"), || Ok(f)).unwrap();
+            let r = easy_part(&fv).unwrap().value().unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!("easy part: {} constraints", cs.num_constraints());
+            r
+        };
+
+        let cs = ConstraintSystem::<Fq377>::new_ref();
+        let rv = Fq12Var::new_witness(ns!(cs, "r"), || Ok(r)).unwrap();
+        let e = hard_part::<ShortX, Fq377>(&rv).unwrap();
+        assert_eq!(
+            e.value().unwrap(),
+            Bls12::<ShortX>::final_exponentiation(&f).unwrap()
+        );
+        assert!(cs.is_satisfied().unwrap());
+        println!("hard part for x = -2: {} constraints", cs.num_constraints());
+    }
+
+    /// A Miller loop cut down to the first bit of x below its leading one,
+    /// so with a doubling and an addition step, followed by the final
+    /// exponentiation for [`ShortX`], against ark-ec: the short version of
+    /// `emulated_pairing`. As in [`final_exponentiation_parts`], the hard
+    /// part gets its own constraint system to stay within memory.
+    #[test]
+    fn short_pairing() {
+        let mut rng = ark_std::test_rng();
+        let p = G1Projective::rand(&mut rng).into_affine();
+        let q = G2Projective::rand(&mut rng).into_affine();
+        let bit = BitIteratorBE::new(Parameters::X)
+            .skip_while(|b| !b)
+            .nth(1)
+            .unwrap();
+        assert!(bit);
+
+        let prepared = G2Prepared::<Parameters>::from(q);
+        let mut expected = Fq12::one();
+        for (c0, mut c1, mut c2) in prepared.ell_coeffs.iter().take(2).copied() {
+            c1.mul_assign_by_fp(&p.x);
+            c2.mul_assign_by_fp(&p.y);
+            expected.mul_by_014(&c0, &c1, &c2);
+        }
+        expected.conjugate();
+
+        let r = {
+            let cs = ConstraintSystem::<Fq377>::new_ref();
+            let pv = G1AffineVar::new_witness(ns!(cs, "p"), || Ok(p)).unwrap();
+            let qv = G2AffineVar::new_witness(ns!(cs, "q"), || Ok(q)).unwrap();
+            let f = miller_loop_bits(&[pv], &[qv], [bit].into_iter())
+                .unwrap()
+                .conjugate()
+                .unwrap();
+            assert_eq!(f.value().unwrap(), expected);
+            let r = easy_part(&f).unwrap().value().unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!(
+                "short miller loop and easy part: {} constraints",
+                cs.num_constraints()
+            );
+            r
+        };
+
+        let cs = ConstraintSystem::<Fq377>::new_ref();
+        let rv = Fq12Var::new_witness(ns!(cs, "r"), || Ok(r)).unwrap();
+        let e = hard_part::<ShortX, Fq377>(&rv).unwrap();
+        assert_eq!(
+            e.value().unwrap(),
+            Bls12::<ShortX>::final_exponentiation(&expected).unwrap()
+        );
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
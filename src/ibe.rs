@@ -0,0 +1,317 @@
+//! Boneh-Franklin identity-based encryption over BLS12-377. The master key
+//! s gives P = s g2, an identity ID gets d = s H(ID) with H hashing to G1,
+//! and a message of Fq elements is masked with Poseidon(e(H(ID), P)^r) next
+//! to U = r g2. The circuit proves that a ciphertext decrypts, under a
+//! private identity key, to the plaintext behind a public commitment.
+use super::{
+    bls::enforce_bls_verify,
+    hash_to_curve::{HashToCurve, HashToCurveGadget},
+};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::ToConstraintField;
+use ark_r1cs_std::{
+    alloc::AllocVar, eq::EqGadget, fields::fp::FpVar, pairing::PairingVar, ToConstraintFieldGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters, PoseidonSponge},
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_std::{
+    marker::PhantomData,
+    rand::{CryptoRng, Rng},
+};
+
+pub struct Ciphertext<I: PairingEngine> {
+    pub u: I::G2Affine,
+    pub v: Vec<I::Fq>,
+}
+
+impl<I: PairingEngine> Clone for Ciphertext<I> {
+    fn clone(&self) -> Self {
+        Self {
+            u: self.u,
+            v: self.v.clone(),
+        }
+    }
+}
+
+/// The master secret and public key, the same as a BLS key pair.
+#[allow(dead_code)]
+pub fn setup<I: PairingEngine, R: Rng + CryptoRng>(rng: &mut R) -> (I::Fr, I::G2Affine) {
+    super::bls::keygen::<I, _>(rng)
+}
+
+/// The identity key s H(ID), which is a BLS signature on the identity.
+#[allow(dead_code)]
+pub fn extract<I: HashToCurve>(
+    params: &PoseidonParameters<I::Fq>,
+    msk: &I::Fr,
+    id: &[I::Fq],
+) -> I::G1Affine {
+    super::bls::sign::<I>(params, msk, id)
+}
+
+/// Squeezes `n` mask elements from the coefficients of a GT element.
+pub fn mask<I>(params: &PoseidonParameters<I::Fq>, k: &I::Fqk, n: usize) -> Vec<I::Fq>
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    let mut sponge = PoseidonSponge::new(params);
+    sponge.absorb(&k.to_field_elements().unwrap());
+    sponge.squeeze_native_field_elements(n)
+}
+
+#[allow(dead_code)]
+pub fn encrypt<I>(
+    params: &PoseidonParameters<I::Fq>,
+    mpk: &I::G2Affine,
+    id: &[I::Fq],
+    m: &[I::Fq],
+    r: &I::Fr,
+) -> Ciphertext<I>
+where
+    I: HashToCurve,
+    I::Fq: Absorb,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    let k = I::pairing(I::hash_to_g1(params, id), mpk.mul(*r));
+    Ciphertext {
+        u: I::G2Affine::prime_subgroup_generator()
+            .mul(*r)
+            .into_affine(),
+        v: m.iter()
+            .zip(mask::<I>(params, &k, m.len()))
+            .map(|(m, k)| *m + k)
+            .collect(),
+    }
+}
+
+#[allow(dead_code)]
+pub fn decrypt<I>(
+    params: &PoseidonParameters<I::Fq>,
+    d: &I::G1Affine,
+    ct: &Ciphertext<I>,
+) -> Vec<I::Fq>
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::Fqk: ToConstraintField<I::Fq>,
+{
+    let k = I::pairing(*d, ct.u);
+    ct.v.iter()
+        .zip(mask::<I>(params, &k, ct.v.len()))
+        .map(|(v, k)| *v - k)
+        .collect()
+}
+
+/// Poseidon hash of a blinding element followed by the plaintext.
+pub fn commit<I>(params: &PoseidonParameters<I::Fq>, m: &[I::Fq], blind: &I::Fq) -> I::Fq
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+{
+    let mut sponge = PoseidonSponge::new(params);
+    sponge.absorb(blind);
+    sponge.absorb(&m);
+    sponge.squeeze_native_field_elements(1).remove(0)
+}
+
+/// Master public key, identity, ciphertext and commitment variables.
+type IbeInputs<I, IV> = (
+    <IV as PairingVar<I>>::G2Var,
+    Vec<FpVar<<I as PairingEngine>::Fq>>,
+    <IV as PairingVar<I>>::G2Var,
+    Vec<FpVar<<I as PairingEngine>::Fq>>,
+    FpVar<<I as PairingEngine>::Fq>,
+);
+
+/// Proves that the public ciphertext for the public identity decrypts to the
+/// committed plaintext. The identity key is a witness checked against the
+/// master public key as a BLS signature on the identity, so the circuit costs
+/// two pairing checks besides the hashing.
+pub struct IbeDecryptCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    mpk: I::G2Affine,
+    id: Vec<I::Fq>,
+    ct: Ciphertext<I>,
+    d: I::G1Affine,
+    m: Vec<I::Fq>,
+    blind: I::Fq,
+    poseidon_params: PoseidonParameters<I::Fq>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> IbeDecryptCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+    I::Fq: Absorb,
+{
+    #[allow(clippy::too_many_arguments)]
+    #[allow(dead_code)]
+    pub fn new(
+        mpk: I::G2Affine,
+        id: Vec<I::Fq>,
+        ct: Ciphertext<I>,
+        d: I::G1Affine,
+        m: Vec<I::Fq>,
+        blind: I::Fq,
+        params: PoseidonParameters<I::Fq>,
+    ) -> Self {
+        Self {
+            mpk,
+            id,
+            ct,
+            d,
+            m,
+            blind,
+            poseidon_params: params,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        mpk: I::G2Affine,
+        id: &[I::Fq],
+        ct: &Ciphertext<I>,
+        commitment: I::Fq,
+    ) -> Result<IbeInputs<I, IV>, SynthesisError> {
+        let mpk = IV::G2Var::new_input(ns!(cs, "mpk"), || Ok(mpk))?;
+        let id = Vec::<FpVar<I::Fq>>::new_input(ns!(cs, "id"), || Ok(id.to_vec()))?;
+        let u = IV::G2Var::new_input(ns!(cs, "u"), || Ok(ct.u))?;
+        let v = Vec::<FpVar<I::Fq>>::new_input(ns!(cs, "v"), || Ok(ct.v.clone()))?;
+        let commitment = FpVar::new_input(ns!(cs, "commitment"), || Ok(commitment))?;
+        Ok((mpk, id, u, v, commitment))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(
+        mpk: I::G2Affine,
+        id: &[I::Fq],
+        ct: &Ciphertext<I>,
+        commitment: I::Fq,
+    ) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), mpk, id, ct, commitment)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for IbeDecryptCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+    IV::GTVar: ToConstraintFieldGadget<I::Fq>,
+    I::Fq: Absorb,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let params = &self.poseidon_params;
+        let commitment = commit::<I>(params, &self.m, &self.blind);
+        let (mpk, id, u, v, commitment) =
+            Self::alloc_inputs(cs.clone(), self.mpk, &self.id, &self.ct, commitment)?;
+        let d = IV::G1Var::new_witness(ns!(cs, "d"), || Ok(self.d))?;
+        let m = Vec::<FpVar<I::Fq>>::new_witness(ns!(cs, "m"), || Ok(self.m))?;
+        let blind = FpVar::new_witness(ns!(cs, "blind"), || Ok(self.blind))?;
+
+        let h = <IV as HashToCurveGadget<I>>::hash_to_g1(params, &id)?;
+        enforce_bls_verify::<I, IV>(&mpk, &h, &d)?;
+
+        let k = IV::pairing(IV::prepare_g1(&d)?, IV::prepare_g2(&u)?)?;
+        let mut sponge = PoseidonSpongeVar::new(cs.clone(), params);
+        sponge.absorb(&k.to_constraint_field()?)?;
+        let mask = sponge.squeeze_field_elements(v.len())?;
+        for ((v, m), k) in v.iter().zip(&m).zip(&mask) {
+            v.enforce_equal(&(m + k))?;
+        }
+
+        let mut sponge = PoseidonSpongeVar::new(cs, params);
+        sponge.absorb(&blind)?;
+        sponge.absorb(&m)?;
+        commitment.enforce_equal(&sponge.squeeze_field_elements(1)?.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq, Fr};
+    use ark_ff::One;
+    use ark_std::UniformRand;
+
+    type Circuit = IbeDecryptCircuit<I, IV>;
+
+    #[test]
+    fn native_ibe() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (msk, mpk) = setup::<I, _>(&mut rng);
+        let id = vec![Fq::from(42u64)];
+        let m = vec![Fq::rand(&mut rng), Fq::rand(&mut rng), Fq::rand(&mut rng)];
+        let ct = encrypt::<I>(&params, &mpk, &id, &m, &Fr::rand(&mut rng));
+        let d = extract::<I>(&params, &msk, &id);
+        assert_eq!(decrypt::<I>(&params, &d, &ct), m);
+        let other = extract::<I>(&params, &msk, &[Fq::from(43u64)]);
+        assert_ne!(decrypt::<I>(&params, &other, &ct), m);
+    }
+
+    #[test]
+    fn ibe_decrypt_circuit() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (msk, mpk) = setup::<I, _>(&mut rng);
+        let id = vec![Fq::from(42u64)];
+        let m = vec![Fq::rand(&mut rng), Fq::rand(&mut rng)];
+        let blind = Fq::rand(&mut rng);
+        let ct = encrypt::<I>(&params, &mpk, &id, &m, &Fr::rand(&mut rng));
+        let d = extract::<I>(&params, &msk, &id);
+        let circuit = |d, m: &Vec<Fq>| {
+            Circuit::new(
+                mpk,
+                id.clone(),
+                ct.clone(),
+                d,
+                m.clone(),
+                blind,
+                params.clone(),
+            )
+        };
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        circuit(d, &m).generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        println!("IBE decryption: {} constraints", cs.num_constraints());
+        assert_eq!(
+            Circuit::public_inputs(mpk, &id, &ct, commit::<I>(&params, &m, &blind))
+                .unwrap()
+                .len(),
+            cs.num_instance_variables() - 1
+        );
+
+        // a key for another identity
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let other = extract::<I>(&params, &msk, &[Fq::from(43u64)]);
+        circuit(other, &m).generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        // a plaintext the ciphertext does not decrypt to
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        circuit(d, &vec![m[0] + Fq::one(), m[1]])
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
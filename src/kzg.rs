@@ -0,0 +1,369 @@
+//! KZG10 polynomial commitments over BLS12-377 with a batched opening
+//! verifier, natively and inside a circuit over BLS12-377 Fq.
+//!
+//! A batch of openings (C_i, z_i, v_i, pi_i) is checked with random challenges
+//! r_i drawn from a Poseidon transcript over the openings:
+//! e(sum r_i (C_i - v_i G + z_i pi_i), H) == e(sum r_i pi_i, beta H)
+//! which costs two Miller loops and one final exponentiation regardless of
+//! the number of openings.
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{BigInteger, One, PrimeField, ToConstraintField, Zero};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    bits::boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    groups::CurveVar,
+    pairing::PairingVar,
+    R1CSVar, ToBitsGadget, ToConstraintFieldGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters, PoseidonSponge},
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_std::{
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+use std::ops::Neg;
+
+/// Size in bits of the batching challenges.
+const CHALLENGE_BITS: usize = 128;
+
+/// Powers of the secret beta in G1 and the G2 elements needed to verify.
+pub struct UniversalParams<I: PairingEngine> {
+    powers_of_g: Vec<I::G1Affine>,
+    vk: VerifierKey<I>,
+}
+
+#[derive(Clone)]
+pub struct VerifierKey<I: PairingEngine> {
+    g: I::G1Affine,
+    h: I::G2Affine,
+    beta_h: I::G2Affine,
+}
+
+/// Claim that the polynomial committed in `commitment` evaluates to `value`
+/// at `point`, with the quotient commitment `proof`.
+#[derive(Clone)]
+pub struct Opening<I: PairingEngine> {
+    pub commitment: I::G1Affine,
+    pub point: I::Fr,
+    pub value: I::Fr,
+    pub proof: I::G1Affine,
+}
+
+pub fn setup<I: PairingEngine, R: Rng + CryptoRng>(
+    rng: &mut R,
+    max_degree: usize,
+) -> UniversalParams<I> {
+    let beta = I::Fr::rand(rng);
+    let g = I::G1Projective::prime_subgroup_generator();
+    let h = I::G2Projective::prime_subgroup_generator();
+    let mut powers = Vec::with_capacity(max_degree + 1);
+    let mut cur = I::Fr::one();
+    for _ in 0..=max_degree {
+        powers.push(g.mul(cur.into_repr()));
+        cur *= beta;
+    }
+    UniversalParams {
+        powers_of_g: I::G1Projective::batch_normalization_into_affine(&powers),
+        vk: VerifierKey {
+            g: g.into_affine(),
+            h: h.into_affine(),
+            beta_h: h.mul(beta.into_repr()).into_affine(),
+        },
+    }
+}
+
+impl<I: PairingEngine> UniversalParams<I> {
+    pub fn vk(&self) -> &VerifierKey<I> {
+        &self.vk
+    }
+
+    /// Commits to the polynomial given by its coefficients in ascending order.
+    pub fn commit(&self, poly: &[I::Fr]) -> I::G1Affine {
+        assert!(poly.len() <= self.powers_of_g.len(), "degree too large");
+        poly.iter()
+            .zip(&self.powers_of_g)
+            .fold(I::G1Projective::zero(), |acc, (c, g)| acc + g.mul(*c))
+            .into_affine()
+    }
+
+    /// Evaluates the polynomial at `point` and commits to the quotient
+    /// (p(X) - p(point)) / (X - point).
+    pub fn open(&self, poly: &[I::Fr], point: I::Fr) -> (I::Fr, I::G1Affine) {
+        // synthetic division: the remainder is the evaluation
+        let mut quotient = vec![I::Fr::zero(); poly.len().saturating_sub(1)];
+        let mut acc = I::Fr::zero();
+        for (i, c) in poly.iter().enumerate().rev() {
+            acc = acc * point + c;
+            if i > 0 {
+                quotient[i - 1] = acc;
+            }
+        }
+        (acc, self.commit(&quotient))
+    }
+}
+
+/// Checks a single opening with e(C - vG + z pi, H) == e(pi, beta H).
+#[allow(dead_code)]
+pub fn verify<I: PairingEngine>(vk: &VerifierKey<I>, opening: &Opening<I>) -> bool {
+    let lhs = opening.commitment.into_projective() - vk.g.mul(opening.value)
+        + opening.proof.mul(opening.point);
+    I::product_of_pairings(&[
+        (lhs.into_affine().into(), vk.h.into()),
+        (opening.proof.neg().into(), vk.beta_h.into()),
+    ])
+    .is_one()
+}
+
+/// Maps a scalar into the base field, which is larger for BLS12-377.
+fn fr_to_fq<I: PairingEngine>(s: &I::Fr) -> I::Fq {
+    I::Fq::from_repr(<I::Fq as PrimeField>::BigInt::from_bits_le(
+        &s.into_repr().to_bits_le(),
+    ))
+    .unwrap()
+}
+
+/// Batching challenges: the transcript absorbs every opening and each
+/// challenge is the low `CHALLENGE_BITS` bits of a squeezed base field element.
+pub fn challenges<I>(params: &PoseidonParameters<I::Fq>, openings: &[Opening<I>]) -> Vec<I::Fr>
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+{
+    let mut sponge = PoseidonSponge::new(params);
+    for o in openings {
+        sponge.absorb(&o.commitment.to_field_elements().unwrap());
+        sponge.absorb(&fr_to_fq::<I>(&o.point));
+        sponge.absorb(&fr_to_fq::<I>(&o.value));
+        sponge.absorb(&o.proof.to_field_elements().unwrap());
+    }
+    sponge
+        .squeeze_native_field_elements(openings.len())
+        .iter()
+        .map(|c| {
+            let bits = c.into_repr().to_bits_le();
+            I::Fr::from_repr(<I::Fr as PrimeField>::BigInt::from_bits_le(
+                &bits[..CHALLENGE_BITS],
+            ))
+            .unwrap()
+        })
+        .collect()
+}
+
+/// Checks all openings with a single two-pairing product.
+#[allow(dead_code)]
+pub fn batch_verify<I>(
+    params: &PoseidonParameters<I::Fq>,
+    vk: &VerifierKey<I>,
+    openings: &[Opening<I>],
+) -> bool
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+{
+    let rs = challenges(params, openings);
+    let mut lhs = I::G1Projective::zero();
+    let mut proofs = I::G1Projective::zero();
+    for (o, r) in openings.iter().zip(rs) {
+        let term = o.commitment.into_projective() - vk.g.mul(o.value) + o.proof.mul(o.point);
+        lhs += term.mul(r.into_repr());
+        proofs += o.proof.mul(r);
+    }
+    I::product_of_pairings(&[
+        (lhs.into_affine().into(), vk.h.into()),
+        (proofs.neg().into_affine().into(), vk.beta_h.into()),
+    ])
+    .is_one()
+}
+
+/// Opening variables: the commitment, point and value are public inputs and
+/// the proof is a witness. Point and value are scalars embedded in the base
+/// field.
+pub struct OpeningVar<I: PairingEngine, IV: PairingVar<I>> {
+    commitment: IV::G1Var,
+    point: FpVar<I::Fq>,
+    value: FpVar<I::Fq>,
+    proof: IV::G1Var,
+}
+
+impl<I, IV> OpeningVar<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    pub fn new(
+        cs: ConstraintSystemRef<I::Fq>,
+        opening: &Opening<I>,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self {
+            commitment: IV::G1Var::new_input(ns!(cs, "commitment"), || Ok(opening.commitment))?,
+            point: FpVar::new_input(ns!(cs, "point"), || Ok(fr_to_fq::<I>(&opening.point)))?,
+            value: FpVar::new_input(ns!(cs, "value"), || Ok(fr_to_fq::<I>(&opening.value)))?,
+            proof: IV::G1Var::new_witness(ns!(cs, "proof"), || Ok(opening.proof))?,
+        })
+    }
+}
+
+/// Little-endian bits of a scalar embedded in the base field, as many as the
+/// scalar field has and checked to encode a scalar below its modulus, instead
+/// of the full decomposition in the base field.
+fn scalar_bits_le<I: PairingEngine>(
+    s: &FpVar<I::Fq>,
+) -> Result<Vec<Boolean<I::Fq>>, SynthesisError> {
+    let n = I::Fr::size_in_bits();
+    let bits = Vec::<Boolean<I::Fq>>::new_witness(ns!(s.cs(), "scalar bits"), || {
+        Ok(s.value()?.into_repr().to_bits_le()[..n].to_vec())
+    })?;
+    Boolean::enforce_smaller_or_equal_than_le(&bits, (-I::Fr::one()).into_repr())?;
+    Boolean::le_bits_to_fp_var(&bits)?.enforce_equal(s)?;
+    Ok(bits)
+}
+
+/// In-circuit version of [`batch_verify`] with a fixed verifier key.
+pub fn enforce_batch_verify<I, IV>(
+    cs: ConstraintSystemRef<I::Fq>,
+    params: &PoseidonParameters<I::Fq>,
+    vk: &VerifierKey<I>,
+    openings: &[OpeningVar<I, IV>],
+) -> Result<(), SynthesisError>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+    IV::G1Var: ToConstraintFieldGadget<I::Fq>,
+{
+    let mut sponge = PoseidonSpongeVar::new(cs, params);
+    for o in openings {
+        sponge.absorb(&o.commitment.to_constraint_field()?)?;
+        sponge.absorb(&o.point)?;
+        sponge.absorb(&o.value)?;
+        sponge.absorb(&o.proof.to_constraint_field()?)?;
+    }
+    let rs = sponge.squeeze_field_elements(openings.len())?;
+
+    let g = IV::G1Var::constant(vk.g.into_projective());
+    let mut lhs = IV::G1Var::zero();
+    let mut proofs = IV::G1Var::zero();
+    for (o, r) in openings.iter().zip(rs) {
+        let r: Vec<Boolean<I::Fq>> = r.to_bits_le()?[..CHALLENGE_BITS].to_vec();
+        let term = o.commitment.clone() - g.scalar_mul_le(scalar_bits_le::<I>(&o.value)?.iter())?
+            + o.proof
+                .scalar_mul_le(scalar_bits_le::<I>(&o.point)?.iter())?;
+        lhs += term.scalar_mul_le(r.iter())?;
+        proofs += o.proof.scalar_mul_le(r.iter())?;
+    }
+    let h = IV::G2Var::constant(vk.h.into_projective());
+    let beta_h = IV::G2Var::constant(vk.beta_h.into_projective());
+    let ps = [IV::prepare_g1(&lhs)?, IV::prepare_g1(&proofs.negate()?)?];
+    let qs = [IV::prepare_g2(&h)?, IV::prepare_g2(&beta_h)?];
+    let ml = IV::miller_loop(&ps, &qs)?;
+    IV::final_exponentiation(&ml)?.enforce_equal(&IV::GTVar::one())
+}
+
+/// Commits to `n` random polynomials of degree `degree` and opens each of
+/// them at a random point.
+pub fn random_openings<I: PairingEngine, R: Rng + CryptoRng>(
+    rng: &mut R,
+    pp: &UniversalParams<I>,
+    n: usize,
+    degree: usize,
+) -> Vec<Opening<I>> {
+    (0..n)
+        .map(|_| {
+            let poly = (0..=degree).map(|_| I::Fr::rand(rng)).collect::<Vec<_>>();
+            let point = I::Fr::rand(rng);
+            let (value, proof) = pp.open(&poly, point);
+            Opening {
+                commitment: pp.commit(&poly),
+                point,
+                value,
+                proof,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq, Fr};
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn native_kzg() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let pp = setup::<I, _>(&mut rng, 15);
+        let poly = (0..16).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let point = Fr::rand(&mut rng);
+        let (value, proof) = pp.open(&poly, point);
+        let expected = poly.iter().rev().fold(Fr::zero(), |acc, c| acc * point + c);
+        assert_eq!(value, expected);
+        let mut opening = Opening {
+            commitment: pp.commit(&poly),
+            point,
+            value,
+            proof,
+        };
+        assert!(verify(pp.vk(), &opening));
+
+        let mut openings = random_openings(&mut rng, &pp, 3, 15);
+        assert!(batch_verify(&params, pp.vk(), &openings));
+        openings[1].value += Fr::one();
+        assert!(!batch_verify(&params, pp.vk(), &openings));
+        opening.value += Fr::one();
+        assert!(!verify(pp.vk(), &opening));
+    }
+
+    #[test]
+    fn kzg_verify_circuit() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let pp = setup::<I, _>(&mut rng, 7);
+        let mut openings = random_openings(&mut rng, &pp, 2, 7);
+        assert!(batch_verify(&params, pp.vk(), &openings));
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let vars = openings
+            .iter()
+            .map(|o| OpeningVar::<I, IV>::new(cs.clone(), o))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        enforce_batch_verify(cs.clone(), &params, pp.vk(), &vars).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        openings[0].value += Fr::one();
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let vars = openings
+            .iter()
+            .map(|o| OpeningVar::<I, IV>::new(cs.clone(), o))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        enforce_batch_verify(cs.clone(), &params, pp.vk(), &vars).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    /// The scalar field modulus, embedded in the base field, has a
+    /// decomposition of the right size but is out of range.
+    #[test]
+    fn scalar_bits_range() {
+        let max = fr_to_fq::<I>(&-Fr::one());
+        for (s, valid) in [(max, true), (max + Fq::one(), false)] {
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            let sv = FpVar::new_input(ns!(cs, "s"), || Ok(s)).unwrap();
+            let bits = scalar_bits_le::<I>(&sv).unwrap();
+            assert_eq!(bits.len(), Fr::size_in_bits());
+            assert_eq!(cs.is_satisfied().unwrap(), valid);
+        }
+    }
+}
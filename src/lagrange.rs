@@ -0,0 +1,150 @@
+//! Lagrange interpolation at zero, used to combine threshold shares in the
+//! exponent: prod D_i^lambda_i with lambda_i = prod_{j != i} x_j / (x_j - x_i).
+//! In circuit the coefficients are computed as non-native scalars from the
+//! participant indices and fed to a multi-exponentiation.
+use ark_ff::{Field, PrimeField};
+use ark_nonnative_field::NonNativeFieldVar;
+use ark_r1cs_std::{alloc::AllocVar, bits::boolean::Boolean, fields::FieldVar, R1CSVar};
+use ark_relations::{ns, r1cs::SynthesisError};
+
+/// Lagrange coefficients at zero for distinct non-zero indices, or `None`
+/// when two indices are equal.
+pub fn lagrange_at_zero<F: Field>(indices: &[F]) -> Option<Vec<F>> {
+    indices
+        .iter()
+        .enumerate()
+        .map(|(i, xi)| {
+            let (num, den) = indices
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold((F::one(), F::one()), |(num, den), (_, xj)| {
+                    (num * xj, den * (*xj - xi))
+                });
+            Some(num * den.inverse()?)
+        })
+        .collect()
+}
+
+/// In-circuit [`lagrange_at_zero`]. With N the product of all indices, each
+/// coefficient is a witness checked by lambda_i * x_i * prod_{j != i} (x_j -
+/// x_i) == N, one multiplication cheaper than an inversion. The indices must
+/// be distinct and non-zero, which the verifier sees since they are public.
+pub fn lagrange_at_zero_var<F: PrimeField, CF: PrimeField>(
+    indices: &[NonNativeFieldVar<F, CF>],
+) -> Result<Vec<NonNativeFieldVar<F, CF>>, SynthesisError> {
+    let cs = indices.cs();
+    let n = indices
+        .iter()
+        .fold(NonNativeFieldVar::one(), |acc, x| acc * x);
+    indices
+        .iter()
+        .enumerate()
+        .map(|(i, xi)| {
+            let den = indices
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold(xi.clone(), |acc, (_, xj)| acc * (xj - xi));
+            // The allocation replaces a failing closure by zero, so repeated
+            // indices are reported before it.
+            let value = match (n.value(), den.value()) {
+                (Ok(n), Ok(den)) => Ok(n * den.inverse().ok_or(SynthesisError::DivisionByZero)?),
+                _ => Err(SynthesisError::AssignmentMissing),
+            };
+            let lambda = NonNativeFieldVar::new_witness(ns!(cs, "lambda"), || value)?;
+            lambda.mul_equals(&den, &n)?;
+            Ok(lambda)
+        })
+        .collect()
+}
+
+/// prod bases_i^exps_i for little-endian exponents, sharing the squarings
+/// between the bases: one squaring per bit of the longest exponent plus one
+/// conditional multiplication per exponent bit.
+pub fn multi_pow_le<F, CF, V>(bases: &[V], exps: &[Vec<Boolean<CF>>]) -> Result<V, SynthesisError>
+where
+    F: Field,
+    CF: PrimeField,
+    V: FieldVar<F, CF>,
+{
+    assert_eq!(bases.len(), exps.len());
+    let len = exps.iter().map(Vec::len).max().unwrap_or(0);
+    let mut res = V::one();
+    for k in (0..len).rev() {
+        res.square_in_place()?;
+        for (base, exp) in bases.iter().zip(exps) {
+            if let Some(bit) = exp.get(k) {
+                let tmp = res.clone() * base;
+                res = bit.select(&tmp, &res)?;
+            }
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::{constraints::Fq12Var, Fq, Fq12, Fr};
+    use ark_r1cs_std::ToBitsGadget;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::{One, UniformRand, Zero};
+
+    #[test]
+    fn native_lagrange() {
+        let mut rng = ark_std::test_rng();
+        // f(x) = a + b x + c x^2 recovered from three points
+        let coeffs = [Fr::rand(&mut rng), Fr::rand(&mut rng), Fr::rand(&mut rng)];
+        let f = |x: Fr| coeffs[0] + coeffs[1] * x + coeffs[2] * x * x;
+        let indices = [Fr::from(2u64), Fr::from(5u64), Fr::from(7u64)];
+        let lambdas = lagrange_at_zero(&indices).unwrap();
+        let secret = indices
+            .iter()
+            .zip(&lambdas)
+            .fold(Fr::zero(), |acc, (x, l)| acc + f(*x) * l);
+        assert_eq!(secret, coeffs[0]);
+        assert_eq!(lambdas.iter().sum::<Fr>(), Fr::one());
+        assert!(lagrange_at_zero(&[Fr::from(2u64), Fr::from(2u64)]).is_none());
+    }
+
+    #[test]
+    fn lagrange_gadgets() {
+        let mut rng = ark_std::test_rng();
+        let indices = [Fr::from(1u64), Fr::from(3u64), Fr::from(4u64)];
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let indices_var =
+            Vec::<NonNativeFieldVar<Fr, Fq>>::new_input(ns!(cs, "indices"), || Ok(indices))
+                .unwrap();
+        let lambdas = lagrange_at_zero_var(&indices_var).unwrap();
+        assert_eq!(
+            lambdas.value().unwrap(),
+            lagrange_at_zero(&indices).unwrap()
+        );
+
+        let bases = [Fq12::rand(&mut rng), Fq12::rand(&mut rng)];
+        let bases_var = Vec::<Fq12Var>::new_witness(ns!(cs, "bases"), || Ok(bases)).unwrap();
+        let exps = lambdas[..2]
+            .iter()
+            .map(|l| l.to_bits_le())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let expected = bases[0].pow(lambdas[0].value().unwrap().into_repr())
+            * bases[1].pow(lambdas[1].value().unwrap().into_repr());
+        assert_eq!(
+            multi_pow_le(&bases_var, &exps).unwrap().value().unwrap(),
+            expected
+        );
+        assert!(cs.is_satisfied().unwrap());
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let repeated = Vec::<NonNativeFieldVar<Fr, Fq>>::new_input(ns!(cs, "indices"), || {
+            Ok([Fr::from(3u64), Fr::from(3u64)])
+        })
+        .unwrap();
+        assert!(matches!(
+            lagrange_at_zero_var(&repeated),
+            Err(SynthesisError::DivisionByZero)
+        ));
+    }
+}
@@ -2,56 +2,69 @@
 extern crate json;
 #[macro_use]
 extern crate lazy_static;
-use ark_ec::{PairingEngine, ProjectiveCurve};
-use ark_ff::{BigInteger, PrimeField};
-use ark_nonnative_field::NonNativeFieldVar;
-use ark_r1cs_std::{
-    alloc::AllocVar,
-    eq::EqGadget,
-    fields::{fp::FpVar, FieldVar},
-    groups::CurveVar,
-    pairing::PairingVar,
-    ToBitsGadget, ToConstraintFieldGadget,
-};
-use ark_relations::{
-    ns,
-    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, Field, SynthesisError},
-};
-use ark_sponge::constraints::CryptographicSpongeVar;
-use ark_sponge::{poseidon::PoseidonParameters, Absorb};
-use ark_sponge::{
-    poseidon::{constraints::PoseidonSpongeVar, PoseidonSponge},
-    CryptographicSponge,
-};
-use ark_std::{
-    marker::PhantomData,
-    rand::{CryptoRng, Rng},
-    UniformRand,
-};
-use eyre::{Result, WrapErr};
-use std::ops::MulAssign;
+mod bls;
 mod bls12377;
 mod bls12381;
+mod dleq;
+mod drand;
+mod elgamal;
+mod emulated;
+mod hash_to_curve;
+mod ibe;
+mod kzg;
+mod lagrange;
+mod limbs;
+mod merkle;
+mod nonnative_cmp;
+mod packing;
+mod pedersen;
 mod poseidon;
+mod recursion;
+mod registry;
+mod threshold;
+mod vrf;
+mod vss;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 enum OpMode {
-    Mul,   // GT * GT
-    GtMul, // Fr * GT
-    GtAdd, // GT + GT
-    Equality,          // GT == GT
-    HashGT(usize),     // H(number of gt elements)
-    HashFr(usize),     // H(number of fr elements)
-    NNAHash(usize),    // H(number of NNA field) -> NNA field
-    G1Mul,             // Fr * G1
-    G2Mul,             // Fr * G2
-    MillerLoop(usize), // miller(G1,G2)
-    FinalExp,          // e(g1,g2)^r
-    Pairing,           // full pairing
-    NNAFieldAddOverFq, // Non native field arithmetic Fr addition in Fq
-    NNAFieldMulOverFq, // Non native field arithmetic Fr multiplication in Fq
-    NNAG1Mul,          // s*G in non native
-    NNAFielAddOverF2,  // Non native field arithmetic Fr over a different unrelated field F2
-                       // e.g. it can be bls12-381's Fr done on bls12-377's Fr
+    Mul,                          // GT * GT
+    GtMul,                        // Fr * GT
+    GtAdd,                        // GT + GT
+    Equality,                     // GT == GT
+    HashGT(usize),                // H(number of gt elements)
+    HashFr(usize),                // H(number of fr elements)
+    NNAHash(usize),               // H(number of NNA field) -> NNA field
+    NNAHashPacked(usize),         // NNAHash absorbing densely packed bits
+    NNAChallenges(usize),         // H(msg) -> NNA Fr challenges(number of challenges)
+    NNAShortChallenges(usize),    // H(msg) -> 128-bit NNA Fr challenges(number of challenges)
+    FqTruncatedChallenges(usize), // H(msg) -> Fq truncated to Fr bits(number of challenges)
+    PedersenCommit(usize),        // pedersen commitment in G1(number of messages)
+    NNAPolyEval(usize),           // horner evaluation in NNA(degree)
+    NNAShamirReconstruct(usize),  // shamir secret from NNA shares(threshold)
+    NNAInnerProduct(usize),       // sum a_i*b_i in NNA reduced once(terms)
+    NNAInnerProductNaive(usize),  // sum a_i*b_i in NNA reduced per product(terms)
+    G1Mul,                        // Fr * G1
+    G2Mul,                        // Fr * G2
+    HashToG1,                     // H(Fq elements) -> G1
+    HashToG2,                     // H(Fq elements) -> G2
+    MillerLoop(usize),            // miller(G1,G2)
+    FinalExp,                     // e(g1,g2)^r
+    Pairing,                      // full pairing
+    Groth16Verify(usize),         // groth16 verifier(number of public inputs)
+    KzgVerify(usize),             // batched kzg verifier(number of openings)
+    GtLagrangeCombine(usize),     // prod D_i^lambda_i(number of shares)
+    MerklePath(usize, usize),     // poseidon merkle path(depth, arity)
+    EmulatedFq12Mul,              // bls12-381 GT * GT emulated
+    EmulatedG1Add,                // bls12-381 G1 + G1 emulated
+    EmulatedG2Add,                // bls12-381 G2 + G2 emulated
+    EmulatedMillerLoop(usize),    // bls12-381 miller(G1,G2) emulated
+    EmulatedFinalExp,             // bls12-381 final exponentiation emulated
+    EmulatedPairing,              // bls12-381 full pairing emulated
+    NNAFieldAddOverFq,            // Non native field arithmetic Fr addition in Fq
+    NNAFieldMulOverFq,            // Non native field arithmetic Fr multiplication in Fq
+    NNAG1Mul,                     // s*G in non native
+    // Non native field arithmetic Fr over a different unrelated field F2
+    // e.g. it can be bls12-381's Fr done on bls12-377's Fr
+    NNAFielAddOverF2,
 }
@@ -0,0 +1,335 @@
+//! Non-native field elements with a caller-chosen limb layout, to compare
+//! representations against the one ark-nonnative-field derives with
+//! `find_parameters`. An element is `num_limbs` limbs of `bits_per_limb`
+//! bits, least significant first, range checked on allocation. A product
+//! a b = q p + r is checked as an identity between the limb polynomials:
+//! a b is interpolated from 2n - 1 evaluations, q p is linear since p is a
+//! constant, and the difference is shown to vanish at 2^bits_per_limb by
+//! propagating a range checked carry from limb to limb.
+use ark_ff::{FpParameters, PrimeField};
+use ark_nonnative_field::{
+    params::{find_parameters, OptimizationType},
+    NonNativeFieldParams,
+};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    bits::boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    R1CSVar,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSystemRef, SynthesisError},
+};
+use ark_std::marker::PhantomData;
+use num_bigint::{BigInt, BigUint};
+
+/// The layout ark-nonnative-field picks for F over CF.
+#[allow(dead_code)]
+pub fn default_params<F: PrimeField, CF: PrimeField>(
+    goal: OptimizationType,
+) -> NonNativeFieldParams {
+    let (num_limbs, bits_per_limb) = find_parameters(CF::size_in_bits(), F::size_in_bits(), goal);
+    NonNativeFieldParams {
+        num_limbs,
+        bits_per_limb,
+    }
+}
+
+fn bits_of(v: &BigUint) -> usize {
+    v.bits() as usize
+}
+
+fn split(v: &BigUint, num_limbs: usize, bits_per_limb: usize) -> Vec<BigUint> {
+    let mask = (BigUint::from(1u8) << bits_per_limb) - 1u8;
+    (0..num_limbs)
+        .map(|i| (v >> (i * bits_per_limb)) & &mask)
+        .collect()
+}
+
+fn to_field<CF: PrimeField>(v: &BigInt) -> CF {
+    let m = CF::from(v.magnitude().clone());
+    if v.sign() == num_bigint::Sign::Minus {
+        -m
+    } else {
+        m
+    }
+}
+
+/// Allocates `bits` little-endian bits of `v` and returns their packing.
+fn alloc_bounded<CF: PrimeField>(
+    cs: ConstraintSystemRef<CF>,
+    v: Option<BigUint>,
+    bits: usize,
+) -> Result<FpVar<CF>, SynthesisError> {
+    let bits = Vec::<Boolean<CF>>::new_witness(ns!(cs, "limb bits"), || {
+        let v = v.ok_or(SynthesisError::AssignmentMissing)?;
+        assert!(bits_of(&v) <= bits);
+        Ok((0..bits).map(|k| v.bit(k as u64)).collect::<Vec<_>>())
+    })?;
+    Boolean::le_bits_to_fp_var(&bits)
+}
+
+#[derive(Clone)]
+pub struct LimbVar<F: PrimeField, CF: PrimeField> {
+    pub limbs: Vec<FpVar<CF>>,
+    params: NonNativeFieldParams,
+    /// Bound on the limbs, which grows with unreduced additions.
+    limb_bits: usize,
+    _f: PhantomData<F>,
+}
+
+impl<F: PrimeField, CF: PrimeField> LimbVar<F, CF> {
+    pub fn new_witness(
+        cs: ConstraintSystemRef<CF>,
+        value: Option<F>,
+        params: &NonNativeFieldParams,
+    ) -> Result<Self, SynthesisError> {
+        let (n, w) = (params.num_limbs, params.bits_per_limb);
+        assert!(n * w >= F::size_in_bits(), "limbs too small for the field");
+        let limbs = split(&value.unwrap_or_default().into(), n, w)
+            .into_iter()
+            .map(|l| alloc_bounded(cs.clone(), value.map(|_| l), w))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            limbs,
+            params: params.clone(),
+            limb_bits: w,
+            _f: PhantomData,
+        })
+    }
+
+    fn integer(&self) -> Result<BigUint, SynthesisError> {
+        self.limbs
+            .iter()
+            .rev()
+            .try_fold(BigUint::default(), |acc, l| {
+                Ok((acc << self.params.bits_per_limb) + Into::<BigUint>::into(l.value()?))
+            })
+    }
+
+    #[allow(dead_code)]
+    pub fn value(&self) -> Result<F, SynthesisError> {
+        Ok(F::from(self.integer()?))
+    }
+
+    /// Limb-wise sum, left unreduced.
+    pub fn add(&self, other: &Self) -> Self {
+        Self {
+            limbs: self
+                .limbs
+                .iter()
+                .zip(&other.limbs)
+                .map(|(a, b)| a + b)
+                .collect(),
+            params: self.params.clone(),
+            limb_bits: self.limb_bits.max(other.limb_bits) + 1,
+            _f: PhantomData,
+        }
+    }
+
+    fn limb_values(&self) -> Result<Vec<BigUint>, SynthesisError> {
+        self.limbs.iter().map(|l| Ok(l.value()?.into())).collect()
+    }
+
+    pub fn mul(&self, other: &Self) -> Result<Self, SynthesisError> {
+        let p: BigUint = F::Params::MODULUS.into();
+        let qr = self
+            .integer()
+            .and_then(|x| Ok(x * other.integer()?))
+            .map(|ab| (&ab / &p, ab % &p));
+        self.mul_with(other, qr)
+    }
+
+    /// [`Self::mul`] with the quotient and the remainder given by the caller.
+    fn mul_with(
+        &self,
+        other: &Self,
+        qr: Result<(BigUint, BigUint), SynthesisError>,
+    ) -> Result<Self, SynthesisError> {
+        let cs = self.limbs.cs().or(other.limbs.cs());
+        let (n, w) = (self.params.num_limbs, self.params.bits_per_limb);
+        let p: BigUint = F::Params::MODULUS.into();
+        let max = |bits: usize| {
+            (0..n).fold(BigUint::default(), |acc, i| {
+                acc + (((BigUint::from(1u8) << bits) - 1u8) << (i * w))
+            })
+        };
+        let nq = bits_of(&(max(self.limb_bits) * max(other.limb_bits) / &p)).div_ceil(w);
+        let np = bits_of(&p).div_ceil(w);
+        let len = (2 * n - 1).max(nq + np - 1);
+        // bound on the coefficients of a b - q p - r
+        let coeff_bits =
+            (self.limb_bits + other.limb_bits).max(2 * w) + bits_of(&(n + nq + 1).into());
+        let carry_bits = coeff_bits - w + 2;
+        assert!(
+            coeff_bits + 2 < CF::size_in_bits() - 1,
+            "limbs too large for the constraint field"
+        );
+
+        // native limbs of a, b, q and r
+        let native = self.limb_values().and_then(|a| {
+            let b = other.limb_values()?;
+            let (q, r) = qr?;
+            Ok((a, b, split(&q, nq, w), split(&r, n, w)))
+        });
+        let alloc_limbs = |limbs: Option<&Vec<BigUint>>, count: usize| {
+            (0..count)
+                .map(|i| alloc_bounded(cs.clone(), limbs.map(|l| l[i].clone()), w))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let q_limbs = alloc_limbs(native.as_ref().ok().map(|v| &v.2), nq)?;
+        let r_limbs = alloc_limbs(native.as_ref().ok().map(|v| &v.3), n)?;
+
+        let conv = |x: &[BigUint], y: &[BigUint], k: usize| {
+            (0..x.len())
+                .filter(|i| k >= *i && k - i < y.len())
+                .map(|i| BigInt::from(&x[i] * &y[k - i]))
+                .sum::<BigInt>()
+        };
+
+        // a b as 2n - 1 coefficients, checked at x = 0..2n - 2
+        let prod = (0..2 * n - 1)
+            .map(|k| {
+                FpVar::new_witness(ns!(cs, "product"), || {
+                    let (a, b, _, _) = native.as_ref().map_err(|e| *e)?;
+                    Ok(to_field::<CF>(&conv(a, b, k)))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let eval = |coeffs: &[FpVar<CF>], x: u64| {
+            coeffs
+                .iter()
+                .rev()
+                .fold(FpVar::zero(), |acc, c| acc * CF::from(x) + c)
+        };
+        for x in 0..(2 * n - 1) as u64 {
+            eval(&self.limbs, x).mul_equals(&eval(&other.limbs, x), &eval(&prod, x))?;
+        }
+
+        // a b - q p - r limb by limb, with carries shifted to be non-negative
+        let p_limbs = split(&p, np, w);
+        let offset = BigInt::from(1u8) << (carry_bits - 1);
+        let shift = CF::from(BigUint::from(1u8) << w);
+        let mut carry = FpVar::zero();
+        let mut carry_value = BigInt::default();
+        for k in 0..len {
+            let mut d = prod.get(k).cloned().unwrap_or_else(FpVar::zero);
+            for i in (0..nq).filter(|i| k >= *i && k - i < np) {
+                d -= &q_limbs[i] * CF::from(p_limbs[k - i].clone());
+            }
+            if let Some(r) = r_limbs.get(k) {
+                d -= r;
+            }
+            d += &carry;
+            if k == len - 1 {
+                d.enforce_equal(&FpVar::zero())?;
+                break;
+            }
+            let next = native.as_ref().ok().map(|(a, b, q, r)| {
+                let r_k = BigInt::from(r.get(k).cloned().unwrap_or_default());
+                let d = conv(a, b, k) - conv(q, &p_limbs, k) - r_k;
+                carry_value = (d + &carry_value) >> w;
+                (&carry_value + &offset).to_biguint().unwrap()
+            });
+            let next = alloc_bounded(cs.clone(), next, carry_bits)? - to_field::<CF>(&offset);
+            d.enforce_equal(&(&next * shift))?;
+            carry = next;
+        }
+
+        Ok(Self {
+            limbs: r_limbs,
+            params: self.params.clone(),
+            limb_bits: w,
+            _f: PhantomData,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn enforce_equal(&self, other: &Self) -> Result<(), SynthesisError> {
+        self.limbs.enforce_equal(&other.limbs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::Fr;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::UniformRand;
+
+    type F381 = ark_bls12_381::Fr;
+
+    #[test]
+    fn limb_mul() {
+        let mut rng = ark_std::test_rng();
+        let layouts = [
+            default_params::<F381, Fr>(OptimizationType::Constraints),
+            default_params::<F381, Fr>(OptimizationType::Weight),
+            NonNativeFieldParams {
+                num_limbs: 3,
+                bits_per_limb: 85,
+            },
+            NonNativeFieldParams {
+                num_limbs: 8,
+                bits_per_limb: 32,
+            },
+        ];
+        for params in layouts {
+            let (a, b, c) = (
+                F381::rand(&mut rng),
+                F381::rand(&mut rng),
+                F381::rand(&mut rng),
+            );
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let alloc = |v| LimbVar::<F381, Fr>::new_witness(cs.clone(), Some(v), &params).unwrap();
+            let (a_var, b_var, c_var) = (alloc(a), alloc(b), alloc(c));
+            let ab = a_var.mul(&b_var).unwrap();
+            assert_eq!(ab.value().unwrap(), a * b);
+            // an unreduced sum as input
+            let abc = ab.add(&c_var).mul(&a_var).unwrap();
+            assert_eq!(abc.value().unwrap(), (a * b + c) * a);
+            abc.enforce_equal(&alloc((a * b + c) * a)).unwrap();
+            assert!(cs.is_satisfied().unwrap());
+
+            abc.enforce_equal(&alloc(a)).unwrap();
+            assert!(!cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn limb_mul_forgeries() {
+        let mut rng = ark_std::test_rng();
+        let params = default_params::<F381, Fr>(OptimizationType::Constraints);
+        let (a, b) = (F381::rand(&mut rng), F381::rand(&mut rng));
+        let p: BigUint = <F381 as PrimeField>::Params::MODULUS.into();
+        let ab = Into::<BigUint>::into(a) * Into::<BigUint>::into(b);
+        let (q, r) = (&ab / &p, &ab % &p);
+        let product = |qr: Option<(BigUint, BigUint)>| {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let alloc = |v| LimbVar::<F381, Fr>::new_witness(cs.clone(), Some(v), &params).unwrap();
+            let (a_var, b_var) = (alloc(a), alloc(b));
+            match qr {
+                Some(qr) => a_var.mul_with(&b_var, Ok(qr)).unwrap(),
+                None => a_var.mul(&b_var).unwrap(),
+            };
+            cs
+        };
+        assert!(product(Some((q.clone(), r.clone())))
+            .is_satisfied()
+            .unwrap());
+        assert!(!product(Some((&q + 1u8, r.clone()))).is_satisfied().unwrap());
+        assert!(!product(Some((q, r + 1u8))).is_satisfied().unwrap());
+
+        // The last witness of a product is the top bit of its last carry:
+        // set to 2, it moves the carry out of its range.
+        let cs = product(None);
+        assert!(cs.is_satisfied().unwrap());
+        {
+            let mut cs = cs.borrow_mut().unwrap();
+            *cs.witness_assignment.last_mut().unwrap() = Fr::from(2u8);
+        }
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,308 @@
+//! Poseidon Merkle trees of arity 2, 4 or 8 and the in-circuit path
+//! verifier. A node is the hash of its children in order, and the leaves are
+//! field elements. A path stores the siblings at each level permuted by the
+//! position of the node, so that the circuit places the node with one layer
+//! of conditional swaps per index bit instead of a selection per child.
+use ark_ff::PrimeField;
+use ark_r1cs_std::{
+    alloc::AllocVar, bits::boolean::Boolean, eq::EqGadget, fields::fp::FpVar, R1CSVar,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters, PoseidonSponge},
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_std::rand::Rng;
+
+pub fn hash_children<F: PrimeField + Absorb>(params: &PoseidonParameters<F>, children: &[F]) -> F {
+    let mut sponge = PoseidonSponge::new(params);
+    sponge.absorb(&children);
+    sponge.squeeze_native_field_elements(1).remove(0)
+}
+
+pub fn hash_children_var<F: PrimeField>(
+    params: &PoseidonParameters<F>,
+    children: &[FpVar<F>],
+) -> Result<FpVar<F>, SynthesisError> {
+    let mut sponge = PoseidonSpongeVar::new(children.cs(), params);
+    sponge.absorb(&children)?;
+    Ok(sponge.squeeze_field_elements(1)?.remove(0))
+}
+
+fn log_arity(arity: usize) -> usize {
+    assert!(matches!(arity, 2 | 4 | 8), "unsupported arity {}", arity);
+    arity.trailing_zeros() as usize
+}
+
+/// Siblings of the node at `digit` in `children`: entry i - 1 is the child
+/// at position digit ^ i.
+fn permuted_siblings<F: Copy>(children: &[F], digit: usize) -> Vec<F> {
+    (1..children.len()).map(|i| children[digit ^ i]).collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct MerklePath<F> {
+    pub index: usize,
+    pub siblings: Vec<Vec<F>>,
+}
+
+impl<F: PrimeField + Absorb> MerklePath<F> {
+    pub fn arity(&self) -> usize {
+        self.siblings.first().map_or(2, |s| s.len() + 1)
+    }
+
+    /// The root of the tree containing `leaf` at this path.
+    pub fn root(&self, params: &PoseidonParameters<F>, leaf: &F) -> F {
+        let arity = self.arity();
+        let mut index = self.index;
+        self.siblings.iter().fold(*leaf, |node, siblings| {
+            let digit = index % arity;
+            index /= arity;
+            let mut children = vec![node; arity];
+            for (i, s) in siblings.iter().enumerate() {
+                children[digit ^ (i + 1)] = *s;
+            }
+            hash_children(params, &children)
+        })
+    }
+}
+
+pub fn verify<F: PrimeField + Absorb>(
+    params: &PoseidonParameters<F>,
+    root: &F,
+    leaf: &F,
+    path: &MerklePath<F>,
+) -> bool {
+    path.root(params, leaf) == *root
+}
+
+pub struct MerkleTree<F> {
+    arity: usize,
+    /// The leaves first and the root last.
+    layers: Vec<Vec<F>>,
+}
+
+impl<F: PrimeField + Absorb> MerkleTree<F> {
+    /// Builds the tree over `arity^depth` leaves.
+    pub fn new(params: &PoseidonParameters<F>, arity: usize, leaves: Vec<F>) -> Self {
+        log_arity(arity);
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let layer = layers.last().unwrap();
+            assert!(
+                layer.len().is_multiple_of(arity),
+                "leaves must be a power of the arity"
+            );
+            let next = layer
+                .chunks(arity)
+                .map(|children| hash_children(params, children))
+                .collect();
+            layers.push(next);
+        }
+        Self { arity, layers }
+    }
+
+    #[allow(dead_code)]
+    pub fn root(&self) -> F {
+        self.layers.last().unwrap()[0]
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    #[allow(dead_code)]
+    pub fn prove(&self, index: usize) -> MerklePath<F> {
+        let mut pos = index;
+        let siblings = self.layers[..self.depth()]
+            .iter()
+            .map(|layer| {
+                let start = pos - pos % self.arity;
+                let siblings =
+                    permuted_siblings(&layer[start..start + self.arity], pos % self.arity);
+                pos /= self.arity;
+                siblings
+            })
+            .collect();
+        MerklePath { index, siblings }
+    }
+}
+
+/// A random leaf and path of the given shape, for benchmarks.
+pub fn random_path<F: PrimeField, R: Rng>(
+    rng: &mut R,
+    arity: usize,
+    depth: usize,
+) -> (F, MerklePath<F>) {
+    let leaf = F::rand(rng);
+    let index = rng.gen_range(0..arity.pow(depth as u32));
+    let siblings = (0..depth)
+        .map(|_| (1..arity).map(|_| F::rand(rng)).collect())
+        .collect();
+    (leaf, MerklePath { index, siblings })
+}
+
+pub struct MerklePathVar<F: PrimeField> {
+    /// Little-endian bits of the index, log2(arity) per level.
+    index_bits: Vec<Boolean<F>>,
+    siblings: Vec<Vec<FpVar<F>>>,
+}
+
+impl<F: PrimeField> MerklePathVar<F> {
+    pub fn new_witness(
+        cs: ConstraintSystemRef<F>,
+        path: &MerklePath<F>,
+    ) -> Result<Self, SynthesisError> {
+        let arity = path.siblings.first().map_or(2, |s| s.len() + 1);
+        let nbits = log_arity(arity) * path.siblings.len();
+        let index_bits = Vec::<Boolean<F>>::new_witness(ns!(cs, "index bits"), || {
+            Ok((0..nbits)
+                .map(|k| path.index >> k & 1 == 1)
+                .collect::<Vec<_>>())
+        })?;
+        let siblings = path
+            .siblings
+            .iter()
+            .map(|s| Vec::<FpVar<F>>::new_witness(ns!(cs, "siblings"), || Ok(s.clone())))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            index_bits,
+            siblings,
+        })
+    }
+
+    /// In-circuit [`MerklePath::root`]. At each level the node starts in
+    /// position 0 followed by the siblings, and swapping the blocks of size
+    /// 2^l whenever bit l of the digit is set moves every entry from i to
+    /// i ^ digit, which is how the siblings were permuted.
+    pub fn root(
+        &self,
+        params: &PoseidonParameters<F>,
+        leaf: &FpVar<F>,
+    ) -> Result<FpVar<F>, SynthesisError> {
+        let arity = self.siblings.first().map_or(2, |s| s.len() + 1);
+        let mut node = leaf.clone();
+        for (siblings, bits) in self
+            .siblings
+            .iter()
+            .zip(self.index_bits.chunks(log_arity(arity)))
+        {
+            let mut children = vec![node];
+            children.extend_from_slice(siblings);
+            for (l, bit) in bits.iter().enumerate() {
+                let step = 1 << l;
+                for i in (0..children.len()).filter(|i| i & step == 0) {
+                    let lo = bit.select(&children[i + step], &children[i])?;
+                    let hi = &children[i] + &children[i + step] - &lo;
+                    children[i] = lo;
+                    children[i + step] = hi;
+                }
+            }
+            node = hash_children_var(params, &children)?;
+        }
+        Ok(node)
+    }
+
+    pub fn enforce_membership(
+        &self,
+        params: &PoseidonParameters<F>,
+        root: &FpVar<F>,
+        leaf: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        self.root(params, leaf)?.enforce_equal(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{Fq, Fr};
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn check_tree<F: PrimeField + Absorb>(params: &PoseidonParameters<F>, arity: usize) {
+        let mut rng = ark_std::test_rng();
+        let leaves = (0..arity.pow(3))
+            .map(|_| F::rand(&mut rng))
+            .collect::<Vec<_>>();
+        let tree = MerkleTree::new(params, arity, leaves.clone());
+        assert_eq!(tree.depth(), 3);
+        for index in [0, 1, arity + 2, leaves.len() - 1] {
+            let path = tree.prove(index);
+            assert!(verify(params, &tree.root(), &leaves[index], &path));
+            assert!(!verify(
+                params,
+                &tree.root(),
+                &(leaves[index] + F::one()),
+                &path
+            ));
+            let wrong = MerklePath {
+                index: index ^ 1,
+                ..path.clone()
+            };
+            assert!(!verify(params, &tree.root(), &leaves[index], &wrong));
+
+            let cs = ConstraintSystem::<F>::new_ref();
+            let root = FpVar::new_input(ns!(cs, "root"), || Ok(tree.root())).unwrap();
+            let leaf = FpVar::new_witness(ns!(cs, "leaf"), || Ok(leaves[index])).unwrap();
+            let path_var = MerklePathVar::new_witness(cs.clone(), &path).unwrap();
+            path_var.enforce_membership(params, &root, &leaf).unwrap();
+            assert!(cs.is_satisfied().unwrap());
+
+            let cs = ConstraintSystem::<F>::new_ref();
+            let root = FpVar::new_input(ns!(cs, "root"), || Ok(tree.root())).unwrap();
+            let leaf = FpVar::new_witness(ns!(cs, "leaf"), || Ok(leaves[index])).unwrap();
+            let path_var = MerklePathVar::new_witness(cs.clone(), &wrong).unwrap();
+            path_var.enforce_membership(params, &root, &leaf).unwrap();
+            assert!(!cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn merkle_tree() {
+        let fq_params = poseidon::get_bls12377_fq_params(2);
+        let fr_params = poseidon::get_bls12377_fr_params();
+        for arity in [2, 4, 8] {
+            check_tree(&fq_params, arity);
+            check_tree(&fr_params, arity);
+        }
+    }
+
+    fn path_cost<F: PrimeField + Absorb>(
+        params: &PoseidonParameters<F>,
+        arity: usize,
+        depth: usize,
+    ) -> usize {
+        let mut rng = ark_std::test_rng();
+        let (leaf, path) = random_path::<F, _>(&mut rng, arity, depth);
+        let cs = ConstraintSystem::<F>::new_ref();
+        let root = FpVar::new_input(ns!(cs, "root"), || Ok(path.root(params, &leaf))).unwrap();
+        let leaf = FpVar::new_witness(ns!(cs, "leaf"), || Ok(leaf)).unwrap();
+        MerklePathVar::new_witness(cs.clone(), &path)
+            .unwrap()
+            .enforce_membership(params, &root, &leaf)
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        cs.num_constraints()
+    }
+
+    #[test]
+    fn bench_merkle_arity() {
+        let fq_params = poseidon::get_bls12377_fq_params(2);
+        let fr_params = poseidon::get_bls12377_fr_params();
+        // 2^24 leaves in each shape
+        for (arity, depth) in [(2, 24), (4, 12), (8, 8)] {
+            println!(
+                "Merkle path, arity {} depth {}: {} constraints over Fq, {} over Fr",
+                arity,
+                depth,
+                path_cost::<Fq>(&fq_params, arity, depth),
+                path_cost::<Fr>(&fr_params, arity, depth)
+            );
+        }
+    }
+}
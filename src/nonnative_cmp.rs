@@ -0,0 +1,90 @@
+//! Comparison of `NonNativeFieldVar`s as integers below the modulus, which
+//! ark-nonnative-field lacks.
+use ark_ff::PrimeField;
+use ark_nonnative_field::NonNativeFieldVar;
+use ark_r1cs_std::{bits::boolean::Boolean, eq::EqGadget, ToBitsGadget};
+use ark_relations::r1cs::SynthesisError;
+use ark_std::cmp::Ordering;
+
+/// Whether `a` compares to `b` as `ordering`, or equal too when
+/// `should_also_check_equality` is set, as integers below the modulus. Both
+/// are decomposed into canonical bits and packed into native chunks small
+/// enough for the unchecked `FpVar` comparison. The chunks are folded from
+/// the least significant up, each one deciding the result unless it is equal
+/// in both, in which case the result of the chunks below is kept.
+pub fn is_cmp<F: PrimeField, CF: PrimeField>(
+    a: &NonNativeFieldVar<F, CF>,
+    b: &NonNativeFieldVar<F, CF>,
+    ordering: Ordering,
+    should_also_check_equality: bool,
+) -> Result<Boolean<CF>, SynthesisError> {
+    let (left, right) = match ordering {
+        Ordering::Less => (a, b),
+        Ordering::Greater => (b, a),
+        Ordering::Equal => return Err(SynthesisError::Unsatisfiable),
+    };
+    // chunks below 2^(m - 2), hence at most (p - 1) / 2
+    let chunk = CF::size_in_bits() - 2;
+    let (left, right) = (left.to_bits_le()?, right.to_bits_le()?);
+    let mut result = Boolean::constant(should_also_check_equality);
+    for (l, r) in left.chunks(chunk).zip(right.chunks(chunk)) {
+        let (l, r) = (
+            Boolean::le_bits_to_fp_var(l)?,
+            Boolean::le_bits_to_fp_var(r)?,
+        );
+        let lt = l.is_cmp_unchecked(&r, Ordering::Less, false)?;
+        result = lt.or(&l.is_eq(&r)?.and(&result)?)?;
+    }
+    Ok(result)
+}
+
+pub fn enforce_cmp<F: PrimeField, CF: PrimeField>(
+    a: &NonNativeFieldVar<F, CF>,
+    b: &NonNativeFieldVar<F, CF>,
+    ordering: Ordering,
+    should_also_check_equality: bool,
+) -> Result<(), SynthesisError> {
+    is_cmp(a, b, ordering, should_also_check_equality)?.enforce_equal(&Boolean::TRUE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::Fr;
+    use ark_r1cs_std::{alloc::AllocVar, R1CSVar};
+    use ark_relations::{ns, r1cs::ConstraintSystem};
+
+    fn check_cmp<F: PrimeField, CF: PrimeField>() {
+        let mut rng = ark_std::test_rng();
+        let (x, y) = (F::rand(&mut rng), F::rand(&mut rng));
+        let (lo, hi) = if x < y { (x, y) } else { (y, x) };
+        let cases = [
+            (lo, hi, Ordering::Less, false, true),
+            (hi, lo, Ordering::Less, false, false),
+            (lo, lo, Ordering::Less, false, false),
+            (lo, lo, Ordering::Less, true, true),
+            (hi, lo, Ordering::Greater, false, true),
+            (lo, hi, Ordering::Greater, true, false),
+            (-F::one(), F::zero(), Ordering::Greater, false, true),
+            (F::one(), F::zero(), Ordering::Greater, false, true),
+        ];
+        for (a, b, ordering, eq, expected) in cases {
+            let cs = ConstraintSystem::<CF>::new_ref();
+            let a = NonNativeFieldVar::<F, CF>::new_witness(ns!(cs, "a"), || Ok(a)).unwrap();
+            let b = NonNativeFieldVar::<F, CF>::new_witness(ns!(cs, "b"), || Ok(b)).unwrap();
+            assert_eq!(
+                is_cmp(&a, &b, ordering, eq).unwrap().value().unwrap(),
+                expected
+            );
+            enforce_cmp(&a, &b, ordering, eq).unwrap();
+            assert_eq!(cs.is_satisfied().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn nonnative_cmp() {
+        check_cmp::<ark_bls12_381::Fr, Fr>();
+        check_cmp::<ark_bls12_381::Fq, Fr>();
+        check_cmp::<Fr, ark_bls12_377::Fq>();
+    }
+}
@@ -0,0 +1,128 @@
+//! Dense packing of non-native field elements into the constraint field, for
+//! absorbing them into a sponge. `to_constraint_field` on a
+//! `NonNativeFieldVar` reduces and decomposes the element on every call and
+//! returns weight-optimized limbs, several per element. Here each element is
+//! decomposed into its canonical bits once, the bits of all elements are
+//! concatenated and cut into chunks of `CF::CAPACITY` bits, so n elements of
+//! m bits take ceil(n m / capacity) constraint field elements. [`pack`]
+//! produces the same elements natively.
+use ark_ff::{BigInteger, FpParameters, PrimeField};
+use ark_nonnative_field::NonNativeFieldVar;
+use ark_r1cs_std::{bits::boolean::Boolean, fields::fp::FpVar, ToBitsGadget};
+use ark_relations::r1cs::SynthesisError;
+
+fn capacity<CF: PrimeField>() -> usize {
+    CF::Params::CAPACITY as usize
+}
+
+/// The `F::size_in_bits()` little-endian bits of `x`.
+pub fn bits<F: PrimeField>(x: &F) -> Vec<bool> {
+    let mut bits = x.into_repr().to_bits_le();
+    bits.truncate(F::size_in_bits());
+    bits
+}
+
+pub fn pack_bits<CF: PrimeField>(bits: &[bool]) -> Vec<CF> {
+    bits.chunks(capacity::<CF>())
+        .map(|c| CF::from_repr(CF::BigInt::from_bits_le(c)).unwrap())
+        .collect()
+}
+
+#[allow(dead_code)]
+pub fn pack<F: PrimeField, CF: PrimeField>(elems: &[F]) -> Vec<CF> {
+    pack_bits(&elems.iter().flat_map(bits).collect::<Vec<_>>())
+}
+
+/// In-circuit [`bits`], reducing `x` once to its canonical form.
+pub fn bits_var<F: PrimeField, CF: PrimeField>(
+    x: &NonNativeFieldVar<F, CF>,
+) -> Result<Vec<Boolean<CF>>, SynthesisError> {
+    let mut bits = x.to_bits_le()?;
+    // the decomposition checks the bits against the modulus, so those above
+    // its size are zero
+    bits.truncate(F::size_in_bits());
+    Ok(bits)
+}
+
+/// In-circuit [`pack_bits`], linear combinations of the bits.
+pub fn pack_bits_var<CF: PrimeField>(
+    bits: &[Boolean<CF>],
+) -> Result<Vec<FpVar<CF>>, SynthesisError> {
+    bits.chunks(capacity::<CF>())
+        .map(Boolean::le_bits_to_fp_var)
+        .collect()
+}
+
+#[allow(dead_code)]
+pub fn pack_var<F: PrimeField, CF: PrimeField>(
+    elems: &[NonNativeFieldVar<F, CF>],
+) -> Result<Vec<FpVar<CF>>, SynthesisError> {
+    let bits = elems
+        .iter()
+        .map(bits_var)
+        .collect::<Result<Vec<_>, _>>()?
+        .concat();
+    pack_bits_var(&bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{Fq, Fr};
+    use ark_r1cs_std::{alloc::AllocVar, R1CSVar, ToConstraintFieldGadget};
+    use ark_relations::{ns, r1cs::ConstraintSystem};
+    use ark_sponge::{
+        constraints::CryptographicSpongeVar,
+        poseidon::{constraints::PoseidonSpongeVar, PoseidonSponge},
+        CryptographicSponge, FieldBasedCryptographicSponge,
+    };
+    use ark_std::UniformRand;
+
+    fn check_packing<F: PrimeField, CF: PrimeField>() {
+        let mut rng = ark_std::test_rng();
+        let mut elems = (0..5).map(|_| F::rand(&mut rng)).collect::<Vec<_>>();
+        elems.push(-F::one());
+        let packed = pack::<F, CF>(&elems);
+        assert_eq!(
+            packed.len(),
+            (elems.len() * F::size_in_bits()).div_ceil(capacity::<CF>())
+        );
+
+        let cs = ConstraintSystem::<CF>::new_ref();
+        let vars =
+            Vec::<NonNativeFieldVar<F, CF>>::new_witness(ns!(cs, "elems"), || Ok(elems.clone()))
+                .unwrap();
+        assert_eq!(pack_var(&vars).unwrap().value().unwrap(), packed);
+        assert!(cs.is_satisfied().unwrap());
+        // fewer elements than the limbs of `to_constraint_field`
+        let limbs = vars[0].to_constraint_field().unwrap().len();
+        assert!(packed.len() < elems.len() * limbs);
+    }
+
+    #[test]
+    fn packing() {
+        check_packing::<Fr, Fq>();
+        check_packing::<ark_bls12_381::Fr, Fr>();
+        check_packing::<ark_bls12_381::Fq, Fr>();
+    }
+
+    #[test]
+    fn packed_absorption() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let elems = (0..3).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let mut sponge = PoseidonSponge::new(&params);
+        sponge.absorb(&pack::<Fr, Fq>(&elems));
+        let expected = sponge.squeeze_native_field_elements(1);
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let vars =
+            Vec::<NonNativeFieldVar<Fr, Fq>>::new_witness(ns!(cs, "elems"), || Ok(elems)).unwrap();
+        let mut sponge = PoseidonSpongeVar::new(cs.clone(), &params);
+        sponge.absorb(&pack_var(&vars).unwrap()).unwrap();
+        let out = sponge.squeeze_field_elements(1).unwrap();
+        assert_eq!(out.value().unwrap(), expected);
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,273 @@
+//! Pedersen vector commitments in G1: C = sum m_i G_i + r H for scalars m_i
+//! and blinding r. The generators are hashed to the curve from a domain tag
+//! and their index, so nobody knows a discrete logarithm relation between
+//! them, and the generators for n messages are a prefix of those for n + 1.
+use super::{elgamal::alloc_scalar_bits, hash_to_curve::HashToCurve};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::PrimeField;
+use ark_r1cs_std::{
+    alloc::AllocVar, bits::boolean::Boolean, eq::EqGadget, groups::CurveVar, pairing::PairingVar,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::poseidon::PoseidonParameters;
+use ark_std::marker::PhantomData;
+
+pub const GENERATOR_DOMAIN: &[u8] = b"PEDERSEN_GENERATORS";
+
+pub struct Parameters<I: PairingEngine> {
+    pub generators: Vec<I::G1Affine>,
+    pub h: I::G1Affine,
+}
+
+impl<I: PairingEngine> Clone for Parameters<I> {
+    fn clone(&self) -> Self {
+        Self {
+            generators: self.generators.clone(),
+            h: self.h,
+        }
+    }
+}
+
+/// Generators for vectors of up to `n` messages, H having index 0 and G_i
+/// index i + 1.
+pub fn setup<I: HashToCurve>(params: &PoseidonParameters<I::Fq>, n: usize) -> Parameters<I> {
+    let tag = I::Fq::from_le_bytes_mod_order(GENERATOR_DOMAIN);
+    let generator = |i: usize| I::hash_to_g1(params, &[tag, I::Fq::from(i as u64)]);
+    Parameters {
+        generators: (1..=n).map(generator).collect(),
+        h: generator(0),
+    }
+}
+
+pub fn commit<I: PairingEngine>(pp: &Parameters<I>, m: &[I::Fr], r: &I::Fr) -> I::G1Affine {
+    assert!(m.len() <= pp.generators.len());
+    m.iter()
+        .zip(&pp.generators)
+        .fold(pp.h.mul(*r), |acc, (m, g)| acc + g.mul(*m))
+        .into_affine()
+}
+
+/// The multiples 2^i g for each bit of a scalar.
+fn powers_of_two<I: PairingEngine>(g: &I::G1Affine, n: usize) -> Vec<I::G1Projective> {
+    let mut g = g.into_projective();
+    (0..n)
+        .map(|_| {
+            let p = g;
+            g.double_in_place();
+            p
+        })
+        .collect()
+}
+
+/// In-circuit [`commit`] for messages and blinding given as little-endian
+/// bits. The generators are constants, so each term is a fixed-base
+/// multiplication adding the precomputed multiples selected by the bits.
+pub fn commit_var<I, IV>(
+    pp: &Parameters<I>,
+    m: &[Vec<Boolean<I::Fq>>],
+    r: &[Boolean<I::Fq>],
+) -> Result<IV::G1Var, SynthesisError>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    assert!(m.len() <= pp.generators.len());
+    // `precomputed_base_multiscalar_mul_le` overwrites its accumulator on
+    // each scalar instead of adding to it, so the terms are summed here
+    let term = |g: &I::G1Affine, bits: &[Boolean<I::Fq>]| {
+        let mut t = IV::G1Var::zero();
+        t.precomputed_base_scalar_mul_le(bits.iter().zip(&powers_of_two::<I>(g, bits.len())))?;
+        Ok::<_, SynthesisError>(t)
+    };
+    let mut acc = term(&pp.h, r)?;
+    for (m, g) in m.iter().zip(&pp.generators) {
+        acc += term(g, m)?;
+    }
+    Ok(acc)
+}
+
+/// Proves knowledge of an opening of a public commitment, the messages and
+/// blinding being witnesses.
+pub struct PedersenCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    pp: Parameters<I>,
+    commitment: I::G1Affine,
+    m: Vec<I::Fr>,
+    r: I::Fr,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> PedersenCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(pp: Parameters<I>, commitment: I::G1Affine, m: Vec<I::Fr>, r: I::Fr) -> Self {
+        Self {
+            pp,
+            commitment,
+            m,
+            r,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        commitment: I::G1Affine,
+    ) -> Result<IV::G1Var, SynthesisError> {
+        IV::G1Var::new_input(ns!(cs, "commitment"), || Ok(commitment.into_projective()))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(commitment: I::G1Affine) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), commitment)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for PedersenCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let commitment = Self::alloc_inputs(cs.clone(), self.commitment)?;
+        let m = self
+            .m
+            .iter()
+            .map(|m| alloc_scalar_bits::<I>(cs.clone(), Some(*m)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let r = alloc_scalar_bits::<I>(cs, Some(self.r))?;
+        commit_var::<I, IV>(&self.pp, &m, &r)?.enforce_equal(&commitment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq, Fr};
+    use ark_ff::{One, Zero};
+    use ark_nonnative_field::NonNativeFieldVar;
+    use ark_r1cs_std::{fields::fp::FpVar, ToConstraintFieldGadget};
+    use ark_sponge::{
+        constraints::CryptographicSpongeVar, poseidon::constraints::PoseidonSpongeVar,
+    };
+    use ark_std::UniformRand;
+
+    type Circuit = PedersenCircuit<I, IV>;
+
+    #[test]
+    fn native_pedersen() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let pp = setup::<I>(&params, 4);
+        assert_eq!(setup::<I>(&params, 2).generators, pp.generators[..2]);
+        assert!(!pp.h.is_zero() && !pp.generators.contains(&pp.h));
+        let m = (0..4).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let r = Fr::rand(&mut rng);
+        let c = commit::<I>(&pp, &m, &r);
+        assert!(!c.is_zero());
+        assert_ne!(commit::<I>(&pp, &m, &(r + Fr::one())), c);
+        // additively homomorphic
+        let m2 = (0..4).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let sum = m.iter().zip(&m2).map(|(a, b)| *a + b).collect::<Vec<_>>();
+        assert_eq!(
+            (c + commit::<I>(&pp, &m2, &r)),
+            commit::<I>(&pp, &sum, &(r + r))
+        );
+    }
+
+    #[test]
+    fn pedersen_circuit() {
+        let mut rng = ark_std::test_rng();
+        let pp = setup::<I>(&poseidon::get_bls12377_fq_params(2), 3);
+        let m = (0..3).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let r = Fr::rand(&mut rng);
+        let c = commit::<I>(&pp, &m, &r);
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(pp.clone(), c, m.clone(), r)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(
+            Circuit::public_inputs(c).unwrap().len(),
+            cs.num_instance_variables() - 1
+        );
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(pp, c, m, r + Fr::one())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    /// Constraints of a Poseidon hash over `n` shares given as non-native
+    /// variables, as in `OpMode::NNAHash`, and over `n` native elements, as
+    /// in `OpMode::HashFr`.
+    fn poseidon_costs(n: usize) -> (usize, usize) {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let shares = (0..n)
+            .map(|_| {
+                NonNativeFieldVar::<Fr, Fq>::new_witness(ns!(cs, "share"), || {
+                    Ok(Fr::rand(&mut rng))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut sponge = PoseidonSpongeVar::new(cs.clone(), &params);
+        for s in &shares {
+            sponge.absorb(&s.to_constraint_field().unwrap()).unwrap();
+        }
+        sponge.squeeze_field_elements(1).unwrap();
+        let nonnative = cs.num_constraints();
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let elems = Vec::<FpVar<Fq>>::new_witness(ns!(cs, "elems"), || {
+            Ok((0..n).map(|_| Fq::rand(&mut rng)).collect::<Vec<_>>())
+        })
+        .unwrap();
+        let mut sponge = PoseidonSpongeVar::new(cs.clone(), &params);
+        sponge.absorb(&elems).unwrap();
+        sponge.squeeze_field_elements(1).unwrap();
+        (nonnative, cs.num_constraints())
+    }
+
+    #[test]
+    fn bench_pedersen() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        for n in [1, 4, 16] {
+            let pp = setup::<I>(&params, n);
+            let m = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+            let r = Fr::rand(&mut rng);
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            Circuit::new(pp.clone(), commit::<I>(&pp, &m, &r), m, r)
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            let (nonnative, native) = poseidon_costs(n);
+            println!(
+                "{} shares: Pedersen {} constraints, Poseidon {} over non-native shares, {} over native elements",
+                n,
+                cs.num_constraints(),
+                nonnative,
+                native
+            );
+        }
+    }
+}
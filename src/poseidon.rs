@@ -1,6 +1,9 @@
-use ark_bls12_377::Fq;
+use ark_bls12_377::{Fq, Fr};
+use ark_ff::PrimeField;
 use ark_sponge::poseidon::PoseidonParameters;
 use json::JsonValue;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use std::str::FromStr;
 lazy_static! {
     // bls12377_rate2_constraints:
@@ -226,6 +229,7 @@ lazy_static! {
     };
 }
 
+#[allow(dead_code)]
 pub fn get_bls12377_fq_params(_rate: usize) -> PoseidonParameters<Fq> {
     let arks = P1["ark"]
         .members()
@@ -251,3 +255,33 @@ pub fn get_bls12377_fq_params(_rate: usize) -> PoseidonParameters<Fq> {
         arks,
     )
 }
+
+/// Poseidon parameters over BLS12-377 Fr with the same shape as the Fq ones
+/// (alpha 17, 8 full and 31 partial rounds). There is no reference vector for
+/// this field, so the parameters are generated deterministically with
+/// [`deterministic_params`].
+#[allow(dead_code)]
+pub fn get_bls12377_fr_params() -> PoseidonParameters<Fr> {
+    deterministic_params(8, 31, 17, 0)
+}
+
+/// Round constants drawn from ChaCha20 seeded with `seed`, whose stream is
+/// fixed across rand releases unlike `StdRng`, and the Cauchy MDS matrix
+/// 1/(x_i + y_j) with x_i = i and y_j = 3 + j, for the width 3 sponge.
+pub fn deterministic_params<F: PrimeField>(
+    full_rounds: u32,
+    partial_rounds: u32,
+    alpha: u64,
+    seed: u64,
+) -> PoseidonParameters<F> {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let ark = PoseidonParameters::random_ark(full_rounds + partial_rounds, &mut rng);
+    let mds = (0..3u64)
+        .map(|i| {
+            (0..3u64)
+                .map(|j| F::from(i + j + 3).inverse().unwrap())
+                .collect()
+        })
+        .collect();
+    PoseidonParameters::new(full_rounds, partial_rounds, alpha, mds, ark)
+}
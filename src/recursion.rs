@@ -0,0 +1,269 @@
+//! Verification of BLS12-377 Groth16 proofs inside a circuit over BLS12-377
+//! Fq, i.e. the scalar field of BW6-761, to plan the cost of recursion, and a
+//! two-layer pipeline: a Poseidon preimage proof on BLS12-377 verified by an
+//! outer circuit proven on BW6-761.
+use ark_crypto_primitives::snark::{constraints::SNARKGadget, BooleanInputVar};
+use ark_ec::PairingEngine;
+use ark_ff::{Field, PrimeField};
+use ark_groth16::{
+    constraints::{Groth16VerifierGadget, ProofVar, VerifyingKeyVar},
+    Groth16, Proof, VerifyingKey,
+};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    bits::boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    pairing::PairingVar,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_snark::SNARK;
+use ark_sponge::{
+    constraints::{AbsorbGadget, CryptographicSpongeVar},
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters, PoseidonSponge},
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_std::{
+    marker::PhantomData,
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+
+/// Inner circuit with a configurable number of public inputs: it proves the
+/// knowledge of a square root of each input.
+pub struct SquareRootsCircuit<F: Field> {
+    roots: Vec<F>,
+}
+
+impl<F: Field> SquareRootsCircuit<F> {
+    pub fn new(roots: Vec<F>) -> Self {
+        Self { roots }
+    }
+
+    pub fn public_inputs(&self) -> Vec<F> {
+        self.roots.iter().map(|r| r.square()).collect()
+    }
+}
+
+impl<F: PrimeField> ConstraintSynthesizer<F> for SquareRootsCircuit<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        for r in self.roots {
+            let x = FpVar::new_input(ns!(cs, "x"), || Ok(r.square()))?;
+            let r = FpVar::new_witness(ns!(cs, "r"), || Ok(r))?;
+            x.enforce_equal(&r.square()?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Inner proof to be verified in circuit with its verifying key and public
+/// inputs.
+pub type InnerProof<I> = (VerifyingKey<I>, Proof<I>, Vec<<I as PairingEngine>::Fr>);
+
+/// Sets up and proves a [`SquareRootsCircuit`] with `n` public inputs.
+pub fn inner_proof<I, R>(rng: &mut R, n: usize) -> InnerProof<I>
+where
+    I: PairingEngine,
+    R: Rng + CryptoRng,
+{
+    let roots = (0..n).map(|_| I::Fr::rand(rng)).collect::<Vec<_>>();
+    let circuit = SquareRootsCircuit::new(roots.clone());
+    let inputs = circuit.public_inputs();
+    let (pk, vk) = Groth16::<I>::circuit_specific_setup(circuit, rng).unwrap();
+    let proof = Groth16::<I>::prove(&pk, SquareRootsCircuit::new(roots), rng).unwrap();
+    assert!(Groth16::<I>::verify(&vk, &inputs, &proof).unwrap());
+    (vk, proof, inputs)
+}
+
+/// Enforces that `proof` verifies under `vk` for the given inner public
+/// inputs. The verifying key is fixed by the circuit, the inner public inputs
+/// are packed into public inputs of the outer circuit and the proof is a
+/// witness.
+pub fn enforce_groth16_verify<I, IV>(
+    cs: ConstraintSystemRef<I::Fq>,
+    vk: &VerifyingKey<I>,
+    inputs: &[I::Fr],
+    proof: &Proof<I>,
+) -> Result<(), SynthesisError>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    let vk = VerifyingKeyVar::<I, IV>::new_constant(ns!(cs, "vk"), vk)?;
+    let inputs = BooleanInputVar::new_input(ns!(cs, "inputs"), || Ok(inputs.to_vec()))?;
+    let proof = ProofVar::<I, IV>::new_witness(ns!(cs, "proof"), || Ok(proof))?;
+    Groth16VerifierGadget::<I, IV>::verify(&vk, &inputs, &proof)?.enforce_equal(&Boolean::TRUE)
+}
+
+/// Knowledge of a preimage of a public Poseidon digest.
+pub struct PoseidonPreimageCircuit<F: PrimeField> {
+    preimage: Vec<F>,
+    poseidon_params: PoseidonParameters<F>,
+}
+
+impl<F: PrimeField + Absorb> PoseidonPreimageCircuit<F> {
+    #[allow(dead_code)]
+    pub fn new(preimage: Vec<F>, params: PoseidonParameters<F>) -> Self {
+        Self {
+            preimage,
+            poseidon_params: params,
+        }
+    }
+
+    pub fn digest(&self) -> F {
+        let mut sponge = PoseidonSponge::new(&self.poseidon_params);
+        sponge.absorb(&self.preimage);
+        sponge.squeeze_native_field_elements(1).remove(0)
+    }
+}
+
+impl<F: PrimeField + Absorb> ConstraintSynthesizer<F> for PoseidonPreimageCircuit<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        let digest = FpVar::new_input(ns!(cs, "digest"), || Ok(self.digest()))?;
+        let preimage = Vec::<FpVar<F>>::new_witness(ns!(cs, "preimage"), || Ok(self.preimage))?;
+        let mut sponge = PoseidonSpongeVar::new(cs.clone(), &self.poseidon_params);
+        sponge.absorb(&preimage.to_sponge_field_elements()?)?;
+        sponge
+            .squeeze_field_elements(1)?
+            .remove(0)
+            .enforce_equal(&digest)
+    }
+}
+
+/// Outer circuit of the recursion: verifies an inner Groth16 proof over
+/// `I` whose public inputs become the public inputs of this circuit.
+pub struct RecursiveCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    inner: InnerProof<I>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> RecursiveCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(inner: InnerProof<I>) -> Self {
+        Self {
+            inner,
+            _iv: PhantomData,
+        }
+    }
+
+    /// Public inputs of the outer circuit, i.e. the inner public inputs
+    /// packed into `I::Fq` elements.
+    #[allow(dead_code)]
+    pub fn public_inputs(inputs: &[I::Fr]) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        BooleanInputVar::<I::Fr, I::Fq>::new_input(ns!(cs, "inputs"), || Ok(inputs.to_vec()))?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for RecursiveCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let (vk, proof, inputs) = self.inner;
+        enforce_groth16_verify::<I, IV>(cs, &vk, &inputs, &proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq, Fr};
+    use ark_bw6_761::BW6_761;
+    use ark_serialize::CanonicalSerialize;
+    use std::time::Instant;
+
+    fn size<S: CanonicalSerialize>(s: &S) -> usize {
+        s.serialized_size()
+    }
+
+    #[test]
+    fn groth16_verify_circuit() {
+        let mut rng = ark_std::test_rng();
+        let (vk, proof, mut inputs) = inner_proof::<I, _>(&mut rng, 2);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        enforce_groth16_verify::<I, IV>(cs.clone(), &vk, &inputs, &proof).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        inputs[0] += Fr::from(1u64);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        enforce_groth16_verify::<I, IV>(cs.clone(), &vk, &inputs, &proof).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn two_layer_recursion() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fr_params();
+        let preimage = vec![Fr::rand(&mut rng), Fr::rand(&mut rng)];
+        let circuit = || PoseidonPreimageCircuit::new(preimage.clone(), params.clone());
+        let digest = circuit().digest();
+
+        // inner layer: Groth16 over BLS12-377
+        let start = Instant::now();
+        let (ipk, ivk) = Groth16::<I>::circuit_specific_setup(circuit(), &mut rng).unwrap();
+        println!("inner BLS12-377 setup: {:?}", start.elapsed());
+        let start = Instant::now();
+        let iproof = Groth16::<I>::prove(&ipk, circuit(), &mut rng).unwrap();
+        println!("inner BLS12-377 proving: {:?}", start.elapsed());
+        let start = Instant::now();
+        assert!(Groth16::<I>::verify(&ivk, &[digest], &iproof).unwrap());
+        println!("inner BLS12-377 verification: {:?}", start.elapsed());
+        println!(
+            "inner sizes: proof {} bytes, vk {} bytes, pk {} bytes",
+            size(&iproof),
+            size(&ivk),
+            size(&ipk)
+        );
+
+        // outer layer: verify the inner proof with Groth16 over BW6-761
+        let inner = (ivk, iproof, vec![digest]);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        RecursiveCircuit::<I, IV>::new(inner.clone())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        println!("outer circuit: {} constraints", cs.num_constraints());
+        let start = Instant::now();
+        let (opk, ovk) = Groth16::<BW6_761>::circuit_specific_setup(
+            RecursiveCircuit::<I, IV>::new(inner.clone()),
+            &mut rng,
+        )
+        .unwrap();
+        println!("outer BW6-761 setup: {:?}", start.elapsed());
+        let start = Instant::now();
+        let oproof =
+            Groth16::<BW6_761>::prove(&opk, RecursiveCircuit::<I, IV>::new(inner), &mut rng)
+                .unwrap();
+        println!("outer BW6-761 proving: {:?}", start.elapsed());
+        println!(
+            "outer sizes: proof {} bytes, vk {} bytes, pk {} bytes",
+            size(&oproof),
+            size(&ovk),
+            size(&opk)
+        );
+
+        // native verification of the outer proof against the inner digest
+        let inputs = RecursiveCircuit::<I, IV>::public_inputs(&[digest]).unwrap();
+        let start = Instant::now();
+        assert!(Groth16::<BW6_761>::verify(&ovk, &inputs, &oproof).unwrap());
+        println!("outer BW6-761 verification: {:?}", start.elapsed());
+        let wrong = RecursiveCircuit::<I, IV>::public_inputs(&[digest + Fr::from(1u64)]).unwrap();
+        assert!(!Groth16::<BW6_761>::verify(&ovk, &wrong, &oproof).unwrap());
+    }
+}
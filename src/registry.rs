@@ -0,0 +1,316 @@
+//! Merkle commitments to lists of BLS12-377 group elements, such as the
+//! registry of GT public keys. A leaf is the Poseidon hash of the element,
+//! either of all its coordinates as in `OpMode::HashGT` or of a compressed
+//! encoding:
+//! - G1 and G2 points keep x and the sign of y;
+//! - GT elements f = c0 + c1 w are unitary, so (1 + c0) / c1 over Fq6
+//!   determines them, halving the coefficients to absorb. It is undefined
+//!   for 1 and -1, the unitary elements with c1 = 0, which have no
+//!   compressed leaf.
+//!
+//! In circuit the compressed encoding of a GT element relies on unitarity,
+//! which is enforced as c0^2 - v c1^2 = 1, v being the non-residue of Fq12
+//! over Fq6, and costs two Fq6 squarings besides the multiplications by
+//! the inverse of c1. The sign of y costs a decomposition of y into bits.
+use super::{
+    hash_to_curve::{hash_to_field, hash_to_field_var, Sgn0, Sgn0Gadget},
+    merkle::{MerklePath, MerklePathVar, MerkleTree},
+};
+use ark_bls12_377::{
+    constraints::{Fq12Var, Fq2Var, Fq6Var, FqVar},
+    g1, g2, Fq, Fq12, Fq6,
+};
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
+use ark_ff::{Field, One, ToConstraintField};
+use ark_r1cs_std::{
+    alloc::AllocVar, eq::EqGadget, fields::FieldVar,
+    groups::curves::short_weierstrass::ProjectiveVar, R1CSVar, ToConstraintFieldGadget,
+};
+use ark_relations::{ns, r1cs::SynthesisError};
+use ark_sponge::poseidon::PoseidonParameters;
+
+// The concrete parameters keep the G1 and G2 impls apart, which the
+// `G1Affine` and `G2Affine` aliases do not.
+type G1Affine = GroupAffine<g1::Parameters>;
+type G2Affine = GroupAffine<g2::Parameters>;
+type G1Var = ProjectiveVar<g1::Parameters, FqVar>;
+type G2Var = ProjectiveVar<g2::Parameters, Fq2Var>;
+
+pub const LEAF_DOMAIN: &[u8] = b"BLS12377_REGISTRY_LEAF";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Encoding {
+    Full,
+    Compressed,
+}
+
+/// Group elements that can be committed to as Merkle leaves.
+pub trait Leaf {
+    /// `None` if the element has no such encoding.
+    fn encode(&self, encoding: Encoding) -> Option<Vec<Fq>>;
+}
+
+/// In-circuit counterpart of [`Leaf`], the output must match the native one,
+/// and elements without an encoding are unsatisfiable.
+pub trait LeafVar {
+    fn encode(&self, encoding: Encoding) -> Result<Vec<FqVar>, SynthesisError>;
+}
+
+impl Leaf for G1Affine {
+    fn encode(&self, encoding: Encoding) -> Option<Vec<Fq>> {
+        match encoding {
+            Encoding::Full => self.to_field_elements(),
+            Encoding::Compressed => Some(vec![self.x, Fq::from(self.y.sgn0())]),
+        }
+    }
+}
+
+impl LeafVar for G1Var {
+    fn encode(&self, encoding: Encoding) -> Result<Vec<FqVar>, SynthesisError> {
+        match encoding {
+            Encoding::Full => self.to_constraint_field(),
+            Encoding::Compressed => {
+                let p = self.to_affine()?;
+                Ok(vec![p.x, p.y.sgn0()?.into()])
+            }
+        }
+    }
+}
+
+impl Leaf for G2Affine {
+    fn encode(&self, encoding: Encoding) -> Option<Vec<Fq>> {
+        match encoding {
+            Encoding::Full => self.to_field_elements(),
+            Encoding::Compressed => Some(vec![self.x.c0, self.x.c1, Fq::from(self.y.sgn0())]),
+        }
+    }
+}
+
+impl LeafVar for G2Var {
+    fn encode(&self, encoding: Encoding) -> Result<Vec<FqVar>, SynthesisError> {
+        match encoding {
+            Encoding::Full => self.to_constraint_field(),
+            Encoding::Compressed => {
+                let p = self.to_affine()?;
+                Ok(vec![p.x.c0, p.x.c1, p.y.sgn0()?.into()])
+            }
+        }
+    }
+}
+
+/// (1 + c0) / c1 for unitary elements, `None` for 1 and -1.
+fn compress_gt(f: &Fq12) -> Option<Fq6> {
+    Some((Fq6::one() + f.c0) * f.c1.inverse()?)
+}
+
+impl Leaf for Fq12 {
+    fn encode(&self, encoding: Encoding) -> Option<Vec<Fq>> {
+        match encoding {
+            Encoding::Full => self.to_field_elements(),
+            Encoding::Compressed => compress_gt(self)?.to_field_elements(),
+        }
+    }
+}
+
+impl LeafVar for Fq12Var {
+    fn encode(&self, encoding: Encoding) -> Result<Vec<FqVar>, SynthesisError> {
+        match encoding {
+            Encoding::Full => self.to_constraint_field(),
+            Encoding::Compressed => {
+                // f conj(f) = c0^2 - v c1^2 = 1, otherwise another element
+                // could share the leaf
+                let v_c1_square = Fq12Var::mul_base_field_by_nonresidue(&self.c1.square()?)?;
+                (self.c0.square()? - v_c1_square).enforce_equal(&Fq6Var::one())?;
+                // m c1 = 1 + c0 holds for any m if f = -1, so c1 must be
+                // invertible
+                let c1_inv = match self.c1.value().map(|c1| c1.inverse()) {
+                    Ok(None) => return Err(SynthesisError::Unsatisfiable),
+                    c1_inv => Fq6Var::new_witness(ns!(self.cs(), "c1_inv"), || {
+                        c1_inv.map(Option::unwrap)
+                    })?,
+                };
+                c1_inv.mul_equals(&self.c1, &Fq6Var::one())?;
+                ((&self.c0 + Fq6::one()) * c1_inv).to_constraint_field()
+            }
+        }
+    }
+}
+
+pub fn leaf_hash<L: Leaf>(
+    params: &PoseidonParameters<Fq>,
+    elem: &L,
+    encoding: Encoding,
+) -> Option<Fq> {
+    Some(hash_to_field(params, LEAF_DOMAIN, &elem.encode(encoding)?, 1)[0])
+}
+
+pub fn leaf_hash_var<L: LeafVar>(
+    params: &PoseidonParameters<Fq>,
+    elem: &L,
+    encoding: Encoding,
+) -> Result<FqVar, SynthesisError> {
+    Ok(hash_to_field_var(params, LEAF_DOMAIN, &elem.encode(encoding)?, 1)?.remove(0))
+}
+
+/// Commits to `arity^depth` elements, `None` if one of them has no leaf.
+#[allow(dead_code)]
+pub fn commit<L: Leaf>(
+    params: &PoseidonParameters<Fq>,
+    elems: &[L],
+    arity: usize,
+    encoding: Encoding,
+) -> Option<MerkleTree<Fq>> {
+    let leaves = elems
+        .iter()
+        .map(|e| leaf_hash(params, e, encoding))
+        .collect::<Option<_>>()?;
+    Some(MerkleTree::new(params, arity, leaves))
+}
+
+#[allow(dead_code)]
+pub fn verify<L: Leaf>(
+    params: &PoseidonParameters<Fq>,
+    root: &Fq,
+    elem: &L,
+    path: &MerklePath<Fq>,
+    encoding: Encoding,
+) -> bool {
+    leaf_hash(params, elem, encoding)
+        .is_some_and(|leaf| super::merkle::verify(params, root, &leaf, path))
+}
+
+/// In-circuit [`verify`].
+#[allow(dead_code)]
+pub fn enforce_inclusion<L: LeafVar>(
+    params: &PoseidonParameters<Fq>,
+    root: &FqVar,
+    elem: &L,
+    path: &MerklePathVar<Fq>,
+    encoding: Encoding,
+) -> Result<(), SynthesisError> {
+    path.enforce_membership(params, root, &leaf_hash_var(params, elem, encoding)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{Bls12_377, G1Projective, G2Projective};
+    use ark_ec::{PairingEngine, ProjectiveCurve};
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::{rand::Rng, UniformRand};
+
+    const ENCODINGS: [Encoding; 2] = [Encoding::Full, Encoding::Compressed];
+
+    fn random_gt<R: Rng>(rng: &mut R) -> Fq12 {
+        Bls12_377::pairing(G1Projective::rand(rng), G2Projective::rand(rng))
+    }
+
+    /// Commits to 16 elements, checks an inclusion proof natively and in
+    /// circuit, and returns the cost of the leaf hash and of the whole proof.
+    fn inclusion<L, V>(elems: &[L], other: &L, encoding: Encoding) -> (usize, usize)
+    where
+        L: Leaf + Clone,
+        V: LeafVar + AllocVar<L, Fq>,
+    {
+        let params = poseidon::get_bls12377_fq_params(2);
+        let tree = commit(&params, elems, 2, encoding).unwrap();
+        let path = tree.prove(5);
+        assert!(verify(&params, &tree.root(), &elems[5], &path, encoding));
+        assert!(!verify(&params, &tree.root(), other, &path, encoding));
+
+        let mut costs = (0, 0);
+        for (elem, ok) in [(&elems[5], true), (other, false)] {
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            let root = FqVar::new_input(ns!(cs, "root"), || Ok(tree.root())).unwrap();
+            let elem = V::new_witness(ns!(cs, "elem"), || Ok(elem.clone())).unwrap();
+            let path = MerklePathVar::new_witness(cs.clone(), &path).unwrap();
+            let before = cs.num_constraints();
+            let leaf = leaf_hash_var(&params, &elem, encoding).unwrap();
+            costs.0 = cs.num_constraints() - before;
+            path.enforce_membership(&params, &root, &leaf).unwrap();
+            costs.1 = cs.num_constraints() - before;
+            assert_eq!(cs.is_satisfied().unwrap(), ok);
+            // the same check in one call
+            enforce_inclusion(&params, &root, &elem, &path, encoding).unwrap();
+            assert_eq!(cs.num_constraints() - before, 2 * costs.1);
+            assert_eq!(cs.is_satisfied().unwrap(), ok);
+        }
+        costs
+    }
+
+    #[test]
+    fn encodings() {
+        let mut rng = ark_std::test_rng();
+        let g1 = G1Projective::rand(&mut rng).into_affine();
+        let g2 = G2Projective::rand(&mut rng).into_affine();
+        let gt = random_gt(&mut rng);
+        // negating y only changes the sign, negating c1 only the compressed
+        // value
+        for encoding in ENCODINGS {
+            assert_ne!(g1.encode(encoding), (-g1).encode(encoding));
+            assert_ne!(g2.encode(encoding), (-g2).encode(encoding));
+            assert_ne!(gt.encode(encoding), gt.inverse().unwrap().encode(encoding));
+        }
+        assert_eq!(g1.encode(Encoding::Compressed).unwrap().len(), 2);
+        assert_eq!(g2.encode(Encoding::Compressed).unwrap().len(), 3);
+        assert_eq!(gt.encode(Encoding::Compressed).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn gt_compression() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let gt = random_gt(&mut rng);
+        let encode = |f: Fq12| {
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            let f = Fq12Var::new_witness(ns!(cs, "f"), || Ok(f)).unwrap();
+            let m = f.encode(Encoding::Compressed).map(|m| m.value().unwrap());
+            (m, cs.is_satisfied().unwrap())
+        };
+        assert_eq!(
+            encode(gt),
+            (Ok(gt.encode(Encoding::Compressed).unwrap()), true)
+        );
+
+        // a non-unitary element with the same (1 + c0) / c1
+        let two = Fq6::one().double();
+        let forged = Fq12::new(two * gt.c0 + Fq6::one(), two * gt.c1);
+        assert_eq!(compress_gt(&forged), compress_gt(&gt));
+        assert!(!encode(forged).1);
+
+        // 1 and -1 have no compressed leaf
+        for f in [Fq12::one(), -Fq12::one()] {
+            assert!(f.encode(Encoding::Compressed).is_none());
+            assert!(leaf_hash(&params, &f, Encoding::Compressed).is_none());
+            assert!(commit(&params, &[f, gt], 2, Encoding::Compressed).is_none());
+            assert_eq!(encode(f).0, Err(SynthesisError::Unsatisfiable));
+        }
+    }
+
+    #[test]
+    fn bench_registry() {
+        let mut rng = ark_std::test_rng();
+        let g1 = (0..17)
+            .map(|_| G1Projective::rand(&mut rng).into_affine())
+            .collect::<Vec<_>>();
+        let g2 = (0..17)
+            .map(|_| G2Projective::rand(&mut rng).into_affine())
+            .collect::<Vec<_>>();
+        let gt = (0..17).map(|_| random_gt(&mut rng)).collect::<Vec<_>>();
+        for encoding in ENCODINGS {
+            let costs = [
+                ("G1", inclusion::<_, G1Var>(&g1[..16], &g1[16], encoding)),
+                ("G2", inclusion::<_, G2Var>(&g2[..16], &g2[16], encoding)),
+                ("GT", inclusion::<_, Fq12Var>(&gt[..16], &gt[16], encoding)),
+            ];
+            for (group, (leaf, total)) in costs {
+                println!(
+                    "{} leaf, {:?} encoding, depth 4: {} constraints for the leaf, {} in total",
+                    group, encoding, leaf, total
+                );
+            }
+        }
+    }
+}
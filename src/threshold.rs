@@ -0,0 +1,390 @@
+//! Threshold BLS over BLS12-377 with a trusted dealer: the group secret is
+//! Shamir shared among n parties, any t partial signatures H(m)^s_i combine
+//! into the group signature by Lagrange interpolation in the exponent, and a
+//! circuit verifies the combination against the group public key.
+use super::bls::{self, enforce_bls_verify};
+use super::hash_to_curve::{HashToCurve, HashToCurveGadget};
+use super::lagrange::{lagrange_at_zero, lagrange_at_zero_var};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, PrimeField, Zero};
+use ark_nonnative_field::{NonNativeFieldMulResultVar, NonNativeFieldVar};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    fields::{fp::FpVar, FieldVar},
+    groups::CurveVar,
+    pairing::PairingVar,
+    ToBitsGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::poseidon::PoseidonParameters;
+use ark_std::{
+    marker::PhantomData,
+    rand::{CryptoRng, Rng},
+    UniformRand,
+};
+
+/// Evaluates the polynomial with coefficients `coeffs`, constant term first.
+pub fn evaluate<F: Field>(coeffs: &[F], x: &F) -> F {
+    coeffs.iter().rev().fold(F::zero(), |acc, c| acc * x + c)
+}
+
+/// Shares `secret` with threshold `t` among `n` parties of indices 1..=n.
+/// Returns the polynomial, secret first, and the (index, share) pairs.
+pub fn shamir_share<F: Field, R: Rng>(
+    rng: &mut R,
+    secret: F,
+    t: usize,
+    n: usize,
+) -> (Vec<F>, Vec<(F, F)>) {
+    assert!(0 < t && t <= n);
+    let mut coeffs = vec![secret];
+    coeffs.extend((1..t).map(|_| F::rand(rng)));
+    let shares = (1..=n as u64)
+        .map(|i| (F::from(i), evaluate(&coeffs, &F::from(i))))
+        .collect();
+    (coeffs, shares)
+}
+
+/// Recovers the secret from any t (index, share) pairs, or `None` when two
+/// indices are equal.
+#[allow(dead_code)]
+pub fn shamir_reconstruct<F: Field>(shares: &[(F, F)]) -> Option<F> {
+    let indices = shares.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+    Some(
+        lagrange_at_zero(&indices)?
+            .iter()
+            .zip(shares)
+            .fold(F::zero(), |acc, (l, (_, s))| acc + *l * s),
+    )
+}
+
+/// In-circuit [`evaluate`] with Horner's rule. Each coefficient is added to
+/// the unreduced product, so a step costs a single reduction.
+pub fn evaluate_var<F: PrimeField, CF: PrimeField>(
+    coeffs: &[NonNativeFieldVar<F, CF>],
+    x: &NonNativeFieldVar<F, CF>,
+) -> Result<NonNativeFieldVar<F, CF>, SynthesisError> {
+    let mut acc = coeffs
+        .last()
+        .cloned()
+        .unwrap_or_else(NonNativeFieldVar::zero);
+    for c in coeffs.iter().rev().skip(1) {
+        acc = (acc.mul_without_reduce(x)? + &NonNativeFieldMulResultVar::from(c)).reduce()?;
+    }
+    Ok(acc)
+}
+
+/// In-circuit [`shamir_reconstruct`]: the products lambda_i s_i are summed
+/// unreduced and reduced once.
+pub fn shamir_reconstruct_var<F: PrimeField, CF: PrimeField>(
+    indices: &[NonNativeFieldVar<F, CF>],
+    shares: &[NonNativeFieldVar<F, CF>],
+) -> Result<NonNativeFieldVar<F, CF>, SynthesisError> {
+    let mut acc = NonNativeFieldMulResultVar::zero();
+    for (l, s) in lagrange_at_zero_var(indices)?.iter().zip(shares) {
+        acc += &l.mul_without_reduce(s)?;
+    }
+    acc.reduce()
+}
+
+/// Keys dealt to a committee: the group key, and per party its index, secret
+/// share and public share g2^s_i.
+#[allow(dead_code)]
+pub struct ThresholdKeys<I: PairingEngine> {
+    pub pk: I::G2Affine,
+    pub shares: Vec<(I::Fr, I::Fr)>,
+    pub pks: Vec<I::G2Affine>,
+}
+
+#[allow(dead_code)]
+pub fn keygen<I: PairingEngine, R: Rng + CryptoRng>(
+    rng: &mut R,
+    t: usize,
+    n: usize,
+) -> ThresholdKeys<I> {
+    let g2 = I::G2Affine::prime_subgroup_generator();
+    let secret = I::Fr::rand(rng);
+    let (coeffs, shares) = shamir_share(rng, secret, t, n);
+    let pks = shares
+        .iter()
+        .map(|(_, s)| g2.mul(*s).into_affine())
+        .collect();
+    ThresholdKeys {
+        pk: g2.mul(coeffs[0]).into_affine(),
+        shares,
+        pks,
+    }
+}
+
+/// A partial signature is a plain BLS signature under the secret share, and
+/// verifies against the public share of the party.
+#[allow(dead_code)]
+pub fn partial_sign<I: HashToCurve>(
+    params: &PoseidonParameters<I::Fq>,
+    share: &I::Fr,
+    msg: &[I::Fq],
+) -> I::G1Affine {
+    bls::sign::<I>(params, share, msg)
+}
+
+/// Interpolates the partial signatures of the given indices at zero, or
+/// `None` when two indices are equal.
+#[allow(dead_code)]
+pub fn combine<I: PairingEngine>(
+    indices: &[I::Fr],
+    partials: &[I::G1Affine],
+) -> Option<I::G1Affine> {
+    Some(
+        lagrange_at_zero(indices)?
+            .iter()
+            .zip(partials)
+            .fold(I::G1Projective::zero(), |acc, (l, p)| acc + p.mul(*l))
+            .into_affine(),
+    )
+}
+
+/// Group key, message and signer index variables.
+type ThresholdInputs<I, IV> = (
+    <IV as PairingVar<I>>::G2Var,
+    Vec<FpVar<<I as PairingEngine>::Fq>>,
+    Vec<NonNativeFieldVar<<I as PairingEngine>::Fr, <I as PairingEngine>::Fq>>,
+);
+
+/// Combines t partial signatures given as witnesses and verifies the result
+/// with one pairing check. The group key, message and signer indices are
+/// public inputs, the indices as non-native scalars; the partial signatures
+/// are not checked one by one since only their combination matters.
+pub struct ThresholdBlsCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    pk: I::G2Affine,
+    msg: Vec<I::Fq>,
+    indices: Vec<I::Fr>,
+    partials: Vec<I::G1Affine>,
+    poseidon_params: PoseidonParameters<I::Fq>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> ThresholdBlsCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(
+        pk: I::G2Affine,
+        msg: Vec<I::Fq>,
+        indices: Vec<I::Fr>,
+        partials: Vec<I::G1Affine>,
+        params: PoseidonParameters<I::Fq>,
+    ) -> Self {
+        assert_eq!(indices.len(), partials.len());
+        Self {
+            pk,
+            msg,
+            indices,
+            partials,
+            poseidon_params: params,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        pk: I::G2Affine,
+        msg: &[I::Fq],
+        indices: &[I::Fr],
+    ) -> Result<ThresholdInputs<I, IV>, SynthesisError> {
+        let pk = IV::G2Var::new_input(ns!(cs, "pk"), || Ok(pk))?;
+        let msg = Vec::<FpVar<I::Fq>>::new_input(ns!(cs, "msg"), || Ok(msg.to_vec()))?;
+        let indices =
+            Vec::<NonNativeFieldVar<I::Fr, I::Fq>>::new_input(ns!(cs, "indices"), || {
+                Ok(indices.to_vec())
+            })?;
+        Ok((pk, msg, indices))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(
+        pk: I::G2Affine,
+        msg: &[I::Fq],
+        indices: &[I::Fr],
+    ) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), pk, msg, indices)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for ThresholdBlsCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let (pk, msg, indices) = Self::alloc_inputs(cs.clone(), self.pk, &self.msg, &self.indices)?;
+        let partials = Vec::<IV::G1Var>::new_witness(ns!(cs, "partials"), || {
+            Ok(self
+                .partials
+                .iter()
+                .map(|p| p.into_projective())
+                .collect::<Vec<_>>())
+        })?;
+        let mut sig = IV::G1Var::zero();
+        for (l, p) in lagrange_at_zero_var(&indices)?.iter().zip(&partials) {
+            let bits = l.to_bits_le()?;
+            sig += p.scalar_mul_le(bits[..I::Fr::size_in_bits()].iter())?;
+        }
+        let h = IV::hash_to_g1(&self.poseidon_params, &msg)?;
+        enforce_bls_verify::<I, IV>(&pk, &h, &sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq, Fr};
+    use ark_r1cs_std::R1CSVar;
+
+    type Circuit = ThresholdBlsCircuit<I, IV>;
+
+    #[test]
+    fn native_threshold_bls() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let keys = keygen::<I, _>(&mut rng, 3, 5);
+        let msg = vec![Fq::rand(&mut rng)];
+        let partials = keys
+            .shares
+            .iter()
+            .map(|(_, s)| partial_sign::<I>(&params, s, &msg))
+            .collect::<Vec<_>>();
+        for (pk, p) in keys.pks.iter().zip(&partials) {
+            assert!(bls::verify::<I>(&params, pk, &msg, p));
+        }
+
+        // any three signers give the same signature
+        let indices = keys.shares.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        let sig = combine::<I>(&indices[..3], &partials[..3]).unwrap();
+        assert!(bls::verify::<I>(&params, &keys.pk, &msg, &sig));
+        let subset = [1, 3, 4];
+        let sig2 = combine::<I>(&subset.map(|i| indices[i]), &subset.map(|i| partials[i])).unwrap();
+        assert_eq!(sig, sig2);
+        // two are not enough
+        let sig = combine::<I>(&indices[..2], &partials[..2]).unwrap();
+        assert!(!bls::verify::<I>(&params, &keys.pk, &msg, &sig));
+
+        let secret = Fr::rand(&mut rng);
+        let (coeffs, shares) = shamir_share(&mut rng, secret, 3, 5);
+        assert_eq!(shamir_reconstruct(&shares[2..]), Some(coeffs[0]));
+        let repeated = [shares[0], shares[0], shares[1]];
+        assert!(shamir_reconstruct(&repeated).is_none());
+    }
+
+    #[test]
+    fn nonnative_shamir_gadgets() {
+        let mut rng = ark_std::test_rng();
+        let secret = Fr::rand(&mut rng);
+        let (coeffs, shares) = shamir_share(&mut rng, secret, 8, 10);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let coeffs_var =
+            Vec::<NonNativeFieldVar<Fr, Fq>>::new_witness(ns!(cs, "coeffs"), || Ok(coeffs))
+                .unwrap();
+        let (x, y) = shares[9];
+        let x_var = NonNativeFieldVar::new_witness(ns!(cs, "x"), || Ok(x)).unwrap();
+        assert_eq!(
+            evaluate_var(&coeffs_var, &x_var).unwrap().value().unwrap(),
+            y
+        );
+
+        let (indices, values): (Vec<_>, Vec<_>) = shares[2..].iter().copied().unzip();
+        let indices =
+            Vec::<NonNativeFieldVar<Fr, Fq>>::new_input(ns!(cs, "indices"), || Ok(indices))
+                .unwrap();
+        let values =
+            Vec::<NonNativeFieldVar<Fr, Fq>>::new_witness(ns!(cs, "shares"), || Ok(values))
+                .unwrap();
+        let before = cs.num_constraints();
+        let lazy = shamir_reconstruct_var(&indices, &values).unwrap();
+        assert_eq!(lazy.value().unwrap(), secret);
+        let lazy_cost = cs.num_constraints() - before;
+
+        // reducing after every product and sum
+        let before = cs.num_constraints();
+        let eager = lagrange_at_zero_var(&indices)
+            .unwrap()
+            .iter()
+            .zip(&values)
+            .fold(NonNativeFieldVar::zero(), |acc, (l, s)| acc + l * s);
+        assert_eq!(eager.value().unwrap(), secret);
+        println!(
+            "Shamir reconstruction from 8 shares: {} constraints lazy, {} eager",
+            lazy_cost,
+            cs.num_constraints() - before
+        );
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    fn threshold_circuit(params: &PoseidonParameters<Fq>, t: usize, n: usize) -> Circuit {
+        let mut rng = ark_std::test_rng();
+        let keys = keygen::<I, _>(&mut rng, t, n);
+        let msg = vec![Fq::rand(&mut rng), Fq::rand(&mut rng)];
+        // the last t parties sign
+        let (indices, partials) = keys.shares[n - t..]
+            .iter()
+            .map(|(i, s)| (*i, partial_sign::<I>(params, s, &msg)))
+            .unzip();
+        Circuit::new(keys.pk, msg, indices, partials, params.clone())
+    }
+
+    #[test]
+    fn threshold_bls_circuit() {
+        let params = poseidon::get_bls12377_fq_params(2);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let circuit = threshold_circuit(&params, 3, 5);
+        let inputs = Circuit::public_inputs(circuit.pk, &circuit.msg, &circuit.indices).unwrap();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(inputs, cs.borrow().unwrap().instance_assignment[1..]);
+
+        // partial signatures attributed to the wrong signers
+        let mut circuit = threshold_circuit(&params, 3, 5);
+        circuit.partials.swap(0, 1);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        // below the threshold
+        let mut circuit = threshold_circuit(&params, 3, 5);
+        circuit.indices.pop();
+        circuit.partials.pop();
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn bench_threshold_bls() {
+        let params = poseidon::get_bls12377_fq_params(2);
+        for t in [2, 4, 8, 16] {
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            threshold_circuit(&params, t, 2 * t)
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            println!(
+                "threshold BLS combination, t = {}: {} constraints",
+                t,
+                cs.num_constraints()
+            );
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! A VRF from BLS signatures over BLS12-377: the proof for an input is the
+//! signature on it, which is unique, and the output is its Poseidon hash. The
+//! circuit verifies a proof for a public key and input and derives the public
+//! output, the statement behind leader election.
+use super::{
+    bls::{enforce_bls_verify, sign, verify as bls_verify},
+    hash_to_curve::{hash_to_field, hash_to_field_var, HashToCurve, HashToCurveGadget},
+};
+use ark_ec::PairingEngine;
+use ark_ff::ToConstraintField;
+use ark_r1cs_std::{
+    alloc::AllocVar, eq::EqGadget, fields::fp::FpVar, pairing::PairingVar, ToConstraintFieldGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_sponge::{poseidon::PoseidonParameters, Absorb};
+use ark_std::marker::PhantomData;
+
+pub const VRF_DOMAIN: &[u8] = b"BLS12377_VRF_OUTPUT";
+
+/// Hashes the coordinates of the proof to the output.
+pub fn output<I>(params: &PoseidonParameters<I::Fq>, proof: &I::G1Affine) -> I::Fq
+where
+    I: PairingEngine,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+{
+    hash_to_field(params, VRF_DOMAIN, &proof.to_field_elements().unwrap(), 1)[0]
+}
+
+/// The output and proof for `input`.
+#[allow(dead_code)]
+pub fn evaluate<I>(
+    params: &PoseidonParameters<I::Fq>,
+    sk: &I::Fr,
+    input: &[I::Fq],
+) -> (I::Fq, I::G1Affine)
+where
+    I: HashToCurve,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+{
+    let proof = sign::<I>(params, sk, input);
+    (output::<I>(params, &proof), proof)
+}
+
+#[allow(dead_code)]
+pub fn verify<I>(
+    params: &PoseidonParameters<I::Fq>,
+    pk: &I::G2Affine,
+    input: &[I::Fq],
+    out: &I::Fq,
+    proof: &I::G1Affine,
+) -> bool
+where
+    I: HashToCurve,
+    I::Fq: Absorb,
+    I::G1Affine: ToConstraintField<I::Fq>,
+{
+    bls_verify::<I>(params, pk, input, proof) && output::<I>(params, proof) == *out
+}
+
+/// Public key, input and output variables.
+type VrfInputs<I, IV> = (
+    <IV as PairingVar<I>>::G2Var,
+    Vec<FpVar<<I as PairingEngine>::Fq>>,
+    FpVar<<I as PairingEngine>::Fq>,
+);
+
+/// Verifies a VRF proof, kept as a witness, for a public key, input and
+/// output given as public inputs.
+pub struct VrfVerifyCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    pk: I::G2Affine,
+    input: Vec<I::Fq>,
+    output: I::Fq,
+    proof: I::G1Affine,
+    poseidon_params: PoseidonParameters<I::Fq>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> VrfVerifyCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(
+        pk: I::G2Affine,
+        input: Vec<I::Fq>,
+        output: I::Fq,
+        proof: I::G1Affine,
+        params: PoseidonParameters<I::Fq>,
+    ) -> Self {
+        Self {
+            pk,
+            input,
+            output,
+            proof,
+            poseidon_params: params,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        pk: I::G2Affine,
+        input: &[I::Fq],
+        output: I::Fq,
+    ) -> Result<VrfInputs<I, IV>, SynthesisError> {
+        let pk = IV::G2Var::new_input(ns!(cs, "pk"), || Ok(pk))?;
+        let input = Vec::<FpVar<I::Fq>>::new_input(ns!(cs, "input"), || Ok(input.to_vec()))?;
+        let output = FpVar::new_input(ns!(cs, "output"), || Ok(output))?;
+        Ok((pk, input, output))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(
+        pk: I::G2Affine,
+        input: &[I::Fq],
+        output: I::Fq,
+    ) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), pk, input, output)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for VrfVerifyCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I> + HashToCurveGadget<I>,
+    IV::G1Var: ToConstraintFieldGadget<I::Fq>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let params = &self.poseidon_params;
+        let (pk, input, output) =
+            Self::alloc_inputs(cs.clone(), self.pk, &self.input, self.output)?;
+        let proof = IV::G1Var::new_witness(ns!(cs, "proof"), || Ok(self.proof))?;
+
+        let h = <IV as HashToCurveGadget<I>>::hash_to_g1(params, &input)?;
+        enforce_bls_verify::<I, IV>(&pk, &h, &proof)?;
+        let coords = proof.to_constraint_field()?;
+        hash_to_field_var(params, VRF_DOMAIN, &coords, 1)?[0].enforce_equal(&output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bls::keygen, poseidon};
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq};
+    use ark_bw6_761::BW6_761;
+    use ark_ff::One;
+    use ark_groth16::Groth16;
+    use ark_snark::SNARK;
+    use ark_std::UniformRand;
+    use std::time::Instant;
+
+    type Circuit = VrfVerifyCircuit<I, IV>;
+
+    #[test]
+    fn native_vrf() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (sk, pk) = keygen::<I, _>(&mut rng);
+        let input = vec![Fq::from(7u64), Fq::rand(&mut rng)];
+        let (out, proof) = evaluate::<I>(&params, &sk, &input);
+        assert!(verify::<I>(&params, &pk, &input, &out, &proof));
+        assert!(!verify::<I>(
+            &params,
+            &pk,
+            &input,
+            &(out + Fq::one()),
+            &proof
+        ));
+        // the output is a function of the key and input
+        assert_eq!(evaluate::<I>(&params, &sk, &input).0, out);
+        let (other, _) = evaluate::<I>(&params, &sk, &[Fq::from(8u64)]);
+        assert_ne!(other, out);
+    }
+
+    #[test]
+    fn vrf_verify_circuit() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (sk, pk) = keygen::<I, _>(&mut rng);
+        let input = vec![Fq::from(7u64), Fq::rand(&mut rng)];
+        let (out, proof) = evaluate::<I>(&params, &sk, &input);
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(pk, input.clone(), out, proof, params.clone())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        println!("BLS VRF verification: {} constraints", cs.num_constraints());
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(pk, input.clone(), out + Fq::one(), proof, params.clone())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        // a valid evaluation on another input
+        let (out, proof) = evaluate::<I>(&params, &sk, &[Fq::from(8u64)]);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(pk, input, out, proof, params)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn vrf_verify_groth16() {
+        let mut rng = ark_std::test_rng();
+        let params = poseidon::get_bls12377_fq_params(2);
+        let (sk, pk) = keygen::<I, _>(&mut rng);
+        let input = vec![Fq::from(7u64)];
+        let (out, proof) = evaluate::<I>(&params, &sk, &input);
+        let circuit = || Circuit::new(pk, input.clone(), out, proof, params.clone());
+
+        let start = Instant::now();
+        let (ppk, pvk) = Groth16::<BW6_761>::circuit_specific_setup(circuit(), &mut rng).unwrap();
+        println!("BW6-761 Groth16 setup: {:?}", start.elapsed());
+        let start = Instant::now();
+        let snark = Groth16::<BW6_761>::prove(&ppk, circuit(), &mut rng).unwrap();
+        println!("BW6-761 Groth16 proving: {:?}", start.elapsed());
+        let inputs = Circuit::public_inputs(pk, &input, out).unwrap();
+        let start = Instant::now();
+        assert!(Groth16::<BW6_761>::verify(&pvk, &inputs, &snark).unwrap());
+        println!("BW6-761 Groth16 verification: {:?}", start.elapsed());
+        let wrong = Circuit::public_inputs(pk, &input, out + Fq::one()).unwrap();
+        assert!(!Groth16::<BW6_761>::verify(&pvk, &wrong, &snark).unwrap());
+    }
+}
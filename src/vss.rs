@@ -0,0 +1,251 @@
+//! Feldman verifiable secret sharing in G1 for the DKG: the dealer publishes
+//! C_j = a_j G for its polynomial a_0 + a_1 x + ... + a_{t-1} x^{t-1}, and a
+//! share s_i is consistent iff s_i G == sum_j i^j C_j. The circuit checks
+//! shares given as non-native scalars against public commitments.
+use super::threshold::shamir_share;
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
+use ark_nonnative_field::NonNativeFieldVar;
+use ark_r1cs_std::{
+    alloc::AllocVar, bits::boolean::Boolean, eq::EqGadget, fields::fp::FpVar, groups::CurveVar,
+    pairing::PairingVar, ToBitsGadget,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError},
+};
+use ark_std::{marker::PhantomData, rand::Rng, UniformRand};
+
+/// Size in bits of the participant indices, which are small integers.
+const INDEX_BITS: usize = 16;
+
+pub fn commit<I: PairingEngine>(coeffs: &[I::Fr]) -> Vec<I::G1Affine> {
+    let g = I::G1Affine::prime_subgroup_generator();
+    coeffs.iter().map(|a| g.mul(*a).into_affine()).collect()
+}
+
+/// The dealer's commitments and the (index, share) pairs.
+pub type Dealing<I> = (
+    Vec<<I as PairingEngine>::G1Affine>,
+    Vec<(<I as PairingEngine>::Fr, <I as PairingEngine>::Fr)>,
+);
+
+/// Shares a random secret with threshold `t` among `n` parties.
+#[allow(dead_code)]
+pub fn deal<I: PairingEngine, R: Rng>(rng: &mut R, t: usize, n: usize) -> Dealing<I> {
+    let secret = I::Fr::rand(rng);
+    let (coeffs, shares) = shamir_share(rng, secret, t, n);
+    (commit::<I>(&coeffs), shares)
+}
+
+/// Checks s G == sum_j i^j C_j, evaluating the right side with Horner's rule.
+#[allow(dead_code)]
+pub fn verify_share<I: PairingEngine>(
+    commitments: &[I::G1Affine],
+    index: &I::Fr,
+    share: &I::Fr,
+) -> bool {
+    let rhs = commitments
+        .iter()
+        .rev()
+        .fold(I::G1Projective::zero(), |acc, c| {
+            acc.mul(index.into_repr()).add_mixed(c)
+        });
+    I::G1Affine::prime_subgroup_generator().mul(*share) == rhs
+}
+
+/// Allocates a participant index as a public input together with its
+/// `INDEX_BITS` little-endian bits. Larger indices are unsatisfiable.
+fn alloc_index<I: PairingEngine>(
+    cs: ConstraintSystemRef<I::Fq>,
+    index: &I::Fr,
+) -> Result<Vec<Boolean<I::Fq>>, SynthesisError> {
+    let repr = index.into_repr();
+    let (&index, high) = repr.as_ref().split_first().unwrap();
+    if index >= 1 << INDEX_BITS || high.iter().any(|l| *l != 0) {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let input = FpVar::new_input(ns!(cs, "index"), || Ok(I::Fq::from(index)))?;
+    let bits = Vec::<Boolean<I::Fq>>::new_witness(ns!(cs, "index bits"), || {
+        Ok((0..INDEX_BITS)
+            .map(|k| index >> k & 1 == 1)
+            .collect::<Vec<_>>())
+    })?;
+    Boolean::le_bits_to_fp_var(&bits)?.enforce_equal(&input)?;
+    Ok(bits)
+}
+
+/// In-circuit [`verify_share`]: Horner's rule only multiplies by the short
+/// index, so the cost is dominated by the full-size s G.
+pub fn enforce_share_verify<I, IV>(
+    commitments: &[IV::G1Var],
+    index: &[Boolean<I::Fq>],
+    share: &NonNativeFieldVar<I::Fr, I::Fq>,
+) -> Result<(), SynthesisError>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    let mut rhs = IV::G1Var::zero();
+    for c in commitments.iter().rev() {
+        rhs = rhs.scalar_mul_le(index.iter())? + c;
+    }
+    let g = IV::G1Var::constant(I::G1Projective::prime_subgroup_generator());
+    let bits = share.to_bits_le()?;
+    g.scalar_mul_le(bits[..I::Fr::size_in_bits()].iter())?
+        .enforce_equal(&rhs)
+}
+
+/// Commitment variables and the bits of each index.
+type FeldmanInputs<I, IV> = (
+    Vec<<IV as PairingVar<I>>::G1Var>,
+    Vec<Vec<Boolean<<I as PairingEngine>::Fq>>>,
+);
+
+/// Checks a set of shares against the dealer's commitments, which are public
+/// inputs along with the indices; the shares are witnesses.
+pub struct FeldmanCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    commitments: Vec<I::G1Affine>,
+    shares: Vec<(I::Fr, I::Fr)>,
+    _iv: PhantomData<IV>,
+}
+
+impl<I, IV> FeldmanCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    #[allow(dead_code)]
+    pub fn new(commitments: Vec<I::G1Affine>, shares: Vec<(I::Fr, I::Fr)>) -> Self {
+        Self {
+            commitments,
+            shares,
+            _iv: PhantomData,
+        }
+    }
+
+    fn alloc_inputs(
+        cs: ConstraintSystemRef<I::Fq>,
+        commitments: &[I::G1Affine],
+        indices: &[I::Fr],
+    ) -> Result<FeldmanInputs<I, IV>, SynthesisError> {
+        let commitments = Vec::<IV::G1Var>::new_input(ns!(cs, "commitments"), || {
+            Ok(commitments
+                .iter()
+                .map(|c| c.into_projective())
+                .collect::<Vec<_>>())
+        })?;
+        let indices = indices
+            .iter()
+            .map(|i| alloc_index::<I>(cs.clone(), i))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((commitments, indices))
+    }
+
+    /// Public inputs in allocation order, as expected by the SNARK verifier.
+    #[allow(dead_code)]
+    pub fn public_inputs(
+        commitments: &[I::G1Affine],
+        indices: &[I::Fr],
+    ) -> Result<Vec<I::Fq>, SynthesisError> {
+        let cs = ConstraintSystem::<I::Fq>::new_ref();
+        let _ = Self::alloc_inputs(cs.clone(), commitments, indices)?;
+        let cs = cs.borrow().unwrap();
+        Ok(cs.instance_assignment[1..].to_vec())
+    }
+}
+
+impl<I, IV> ConstraintSynthesizer<I::Fq> for FeldmanCircuit<I, IV>
+where
+    I: PairingEngine,
+    IV: PairingVar<I>,
+{
+    fn generate_constraints(self, cs: ConstraintSystemRef<I::Fq>) -> Result<(), SynthesisError> {
+        let indices = self.shares.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        let (commitments, indices) = Self::alloc_inputs(cs.clone(), &self.commitments, &indices)?;
+        for (index, (_, share)) in indices.iter().zip(&self.shares) {
+            let share = NonNativeFieldVar::new_witness(ns!(cs, "share"), || Ok(share))?;
+            enforce_share_verify::<I, IV>(&commitments, index, &share)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::{constraints::PairingVar as IV, Bls12_377 as I, Fq, Fr};
+    use ark_std::One;
+
+    type Circuit = FeldmanCircuit<I, IV>;
+
+    #[test]
+    fn native_feldman() {
+        let mut rng = ark_std::test_rng();
+        let (commitments, shares) = deal::<I, _>(&mut rng, 3, 5);
+        for (i, s) in &shares {
+            assert!(verify_share::<I>(&commitments, i, s));
+            assert!(!verify_share::<I>(&commitments, i, &(*s + Fr::one())));
+        }
+        let (i, s) = shares[0];
+        assert!(!verify_share::<I>(&commitments, &(i + Fr::one()), &s));
+    }
+
+    #[test]
+    fn feldman_circuit() {
+        let mut rng = ark_std::test_rng();
+        let (commitments, mut shares) = deal::<I, _>(&mut rng, 3, 5);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(commitments.clone(), shares.clone())
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        let indices = shares.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(
+            Circuit::public_inputs(&commitments, &indices)
+                .unwrap()
+                .len(),
+            cs.num_instance_variables() - 1
+        );
+
+        shares[2].1 += Fr::one();
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        Circuit::new(commitments.clone(), shares)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        // indices beyond INDEX_BITS, in the low limb or above it
+        for index in [Fr::from(1u64 << INDEX_BITS), Fr::from(1u128 << 64)] {
+            assert!(matches!(
+                Circuit::public_inputs(&commitments, &[index]),
+                Err(SynthesisError::Unsatisfiable)
+            ));
+        }
+    }
+
+    #[test]
+    fn bench_feldman() {
+        let mut rng = ark_std::test_rng();
+        for t in [2, 4, 8, 16] {
+            for m in [1, 4, 16] {
+                let (commitments, shares) = deal::<I, _>(&mut rng, t, m.max(t));
+                let cs = ConstraintSystem::<Fq>::new_ref();
+                Circuit::new(commitments, shares[..m].to_vec())
+                    .generate_constraints(cs.clone())
+                    .unwrap();
+                assert!(cs.is_satisfied().unwrap());
+                println!(
+                    "Feldman VSS, t = {}, {} shares: {} constraints",
+                    t,
+                    m,
+                    cs.num_constraints()
+                );
+            }
+        }
+    }
+}